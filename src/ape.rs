@@ -0,0 +1,75 @@
+//! A minimal APEv2 tag reader, for the tags foobar2000 and friends append to
+//! MP3 files, which none of the MP3 crates read.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+const PREAMBLE: &[u8; 8] = b"APETAGEX";
+const FOOTER_LEN: usize = 32;
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+/// The length of the APEv2 tag at the very end of `data`, header included, or 0
+/// if there isn't one.
+pub(crate) fn len(data: &[u8]) -> usize {
+    let footer = match data.len().checked_sub(FOOTER_LEN) {
+        Some(start) if data[start..].starts_with(PREAMBLE) => &data[start..],
+        _ => return 0,
+    };
+
+    let size = u32_le(footer, 12).unwrap_or_default() as usize;
+    // bit 31 of the flags tells if the tag also has a header, as big as the footer
+    let header_len = match u32_le(footer, 20) {
+        Some(flags) if flags >> 31 != 0 => FOOTER_LEN,
+        _ => 0,
+    };
+
+    size.saturating_add(header_len).min(data.len())
+}
+
+/// The text items of the APEv2 tag at the very end of `data`, keyed by their
+/// uppercased keys. Multiple values of an item are null-separated, and only the
+/// first is kept.
+pub(crate) fn items(data: &[u8]) -> Option<HashMap<String, String>> {
+    let footer = data.get(data.len().checked_sub(FOOTER_LEN)?..)?;
+    if footer.get(..8)? != PREAMBLE {
+        return None;
+    }
+
+    // the tag size covers the items and the footer, but not the header
+    let size = u32_le(footer, 12)? as usize;
+    let count = u32_le(footer, 16)?;
+    let items = data.get(data.len().checked_sub(size)?..data.len() - FOOTER_LEN)?;
+
+    let mut tags = HashMap::new();
+    let mut pos = 0;
+
+    for _ in 0..count {
+        let value_len = u32_le(items, pos)? as usize;
+        let flags = u32_le(items, pos + 4)?;
+        pos += 8;
+
+        let key_len = items.get(pos..)?.iter().position(|&byte| byte == 0)?;
+        let key = String::from_utf8_lossy(&items[pos..pos + key_len]).to_ascii_uppercase();
+        pos += key_len + 1;
+
+        let value = items.get(pos..pos.checked_add(value_len)?)?;
+        pos += value_len;
+
+        // bits 1 and 2 tell UTF-8 text apart from binary data and links
+        if flags >> 1 & 0b11 != 0 {
+            continue;
+        }
+
+        let value = String::from_utf8_lossy(value);
+        if let Some(value) = value.split('\0').next().filter(|value| !value.is_empty()) {
+            tags.insert(key, String::from(value));
+        }
+    }
+
+    Some(tags)
+}
@@ -0,0 +1,545 @@
+//! A minimal ISO-BMFF box walker, for the atoms that `mp4parse` doesn't expose.
+
+use std::convert::{TryFrom, TryInto};
+use std::ops::Range;
+
+pub(crate) struct Atom<'a> {
+    pub(crate) name: [u8; 4],
+    pub(crate) data: &'a [u8],
+    /// The byte range of the whole atom, header included, within its parent.
+    pub(crate) range: Range<usize>,
+    /// Whether the atom's declared size runs past the end of the buffer.
+    pub(crate) truncated: bool,
+}
+
+pub(crate) struct Atoms<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Atoms<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for Atoms<'a> {
+    type Item = Atom<'a>;
+
+    fn next(&mut self) -> Option<Atom<'a>> {
+        let header = self.data.get(self.pos..self.pos.checked_add(8)?)?;
+        let name = header[4..].try_into().ok()?;
+
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            // the box extends to the end of the buffer
+            0 => (8, self.data.len() - self.pos),
+            // 64-bit size follows the name
+            1 => {
+                let size = self.data.get(self.pos + 8..self.pos + 16)?;
+                let size = u64::from_be_bytes(size.try_into().ok()?);
+                (16, usize::try_from(size).ok()?)
+            }
+            size => (8, size as usize),
+        };
+
+        if size < header_len {
+            return None;
+        }
+
+        let start = self.pos + header_len;
+        let end = self.pos.saturating_add(size).min(self.data.len());
+        let truncated = end - self.pos < size;
+        let range = self.pos..end;
+        self.pos = end;
+
+        Some(Atom {
+            name,
+            data: self.data.get(start..end)?,
+            range,
+            truncated,
+        })
+    }
+}
+
+/// Finds the first atom named `name` among the direct children of `data`.
+pub(crate) fn child<'a>(data: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    Atoms::new(data)
+        .find(|atom| &atom.name == name)
+        .map(|atom| atom.data)
+}
+
+/// Walks down the atom tree following `path`.
+pub(crate) fn find<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |data, name| child(data, name))
+}
+
+/// The major brand and compatible brands of the `ftyp` atom, e.g. `M4A ` and
+/// `isom`, padding included.
+pub(crate) fn brands(data: &[u8]) -> Option<(String, Vec<String>)> {
+    let ftyp = child(data, b"ftyp")?;
+    let brand = |brand: &[u8]| String::from_utf8_lossy(brand).into_owned();
+
+    // the minor version sits between the major and compatible brands
+    let major_brand = brand(ftyp.get(..4)?);
+    let compatible_brands = ftyp.get(8..)?.chunks_exact(4).map(brand).collect();
+
+    Some((major_brand, compatible_brands))
+}
+
+/// The iTunes metadata item list, at `moov.udta.meta.ilst`.
+pub(crate) fn ilst(data: &[u8]) -> Option<&[u8]> {
+    let meta = find(data, &[b"moov", b"udta", b"meta"])?;
+
+    // ISO `meta` is a full box with a version/flags header, while QuickTime's
+    // isn't, so only skip it if it isn't followed by `hdlr`
+    let meta = match meta.get(4..8) {
+        Some(b"hdlr") => meta,
+        _ => meta.get(4..)?,
+    };
+
+    child(meta, b"ilst")
+}
+
+/// The payload of an item's `data` atom, without the type and locale header.
+fn item_value(item: &[u8]) -> Option<&[u8]> {
+    child(item, b"data")?.get(8..)
+}
+
+/// The value of the item named `name`, e.g. `cnID`.
+pub(crate) fn item<'a>(ilst: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    item_value(child(ilst, name)?)
+}
+
+/// Whether the payload of a freeform (`----`) item has the given `mean` and `name`.
+fn is_freeform(item: &[u8], mean: &str, name: &str) -> bool {
+    // `mean` and `name` are full boxes, so their strings follow a 4 byte header
+    let matches = |atom: &[u8; 4], value: &str| {
+        child(item, atom).and_then(|data| data.get(4..)) == Some(value.as_bytes())
+    };
+
+    matches(b"mean", mean) && matches(b"name", name)
+}
+
+/// The value of a freeform (`----`) item with the given `mean` and `name`.
+pub(crate) fn freeform<'a>(ilst: &'a [u8], mean: &str, name: &str) -> Option<&'a [u8]> {
+    Atoms::new(ilst)
+        .filter(|atom| &atom.name == b"----")
+        .find(|atom| is_freeform(atom.data, mean, name))
+        .and_then(|atom| item_value(atom.data))
+}
+
+/// The images of the `covr` item, without their `data` atoms' type and locale
+/// header.
+pub(crate) fn covers(ilst: &[u8]) -> impl Iterator<Item = &[u8]> {
+    Atoms::new(child(ilst, b"covr").unwrap_or_default())
+        .filter(|atom| &atom.name == b"data")
+        .filter_map(|atom| atom.data.get(8..))
+}
+
+fn atom(name: &[u8; 4], payload: &[u8]) -> Option<Vec<u8>> {
+    let size = u32::try_from(payload.len().checked_add(8)?).ok()?;
+
+    let mut atom = Vec::with_capacity(payload.len() + 8);
+    atom.extend_from_slice(&size.to_be_bytes());
+    atom.extend_from_slice(name);
+    atom.extend_from_slice(payload);
+    Some(atom)
+}
+
+fn full_atom(name: &[u8; 4], payload: &[u8]) -> Option<Vec<u8>> {
+    atom(name, &[&[0; 4], payload].concat())
+}
+
+/// The four character code of the first sample entry of the first audio track
+/// (or failing that, of the first track), e.g. `mp4a` or `alac`.
+pub(crate) fn first_sample_entry(data: &[u8]) -> Option<[u8; 4]> {
+    let traks = Atoms::new(child(data, b"moov")?)
+        .filter(|atom| &atom.name == b"trak")
+        .collect::<Vec<_>>();
+    // `hdlr` is a full box, with a pre-defined field before the handler type
+    let is_audio = |trak: &Atom<'_>| {
+        find(trak.data, &[b"mdia", b"hdlr"]).and_then(|hdlr| hdlr.get(8..12)) == Some(b"soun")
+    };
+
+    let trak = traks.iter().find(|trak| is_audio(trak)).or(traks.first())?;
+    let stsd = find(trak.data, &[b"mdia", b"minf", b"stbl", b"stsd"])?;
+
+    // after the version, flags and entry count
+    Atoms::new(stsd.get(8..)?).next().map(|entry| entry.name)
+}
+
+/// The UTF-8 text items of `ilst`, keyed by their four character code (read as
+/// Latin-1, so `\xA9nam` is `©nam`) or, for freeform items, by their `name`.
+pub(crate) fn text_items(ilst: &[u8]) -> Vec<(String, String)> {
+    Atoms::new(ilst)
+        .filter_map(|item| {
+            let data = child(item.data, b"data")?;
+            // well-known type 1 is UTF-8 text
+            if data.get(..4)? != [0, 0, 0, 1] {
+                return None;
+            }
+
+            let key = if &item.name == b"----" {
+                String::from_utf8_lossy(child(item.data, b"name")?.get(4..)?).into_owned()
+            } else {
+                item.name.iter().copied().map(char::from).collect()
+            };
+
+            Some((key, String::from_utf8_lossy(data.get(8..)?).into_owned()))
+        })
+        .collect()
+}
+
+/// ISO 639-2 codes of the Macintosh language codes, up to Simplified Chinese.
+const MAC_LANGUAGES: [&str; 34] = [
+    "eng", "fra", "deu", "ita", "nld", "swe", "spa", "dan", "por", "nor", "heb", "jpn", "ara",
+    "fin", "ell", "isl", "mlt", "tur", "hrv", "zho", "urd", "hin", "tha", "kor", "lit", "pol",
+    "hun", "est", "lav", "smi", "fao", "fas", "rus", "zho",
+];
+
+/// Decodes a QuickTime language code: either a Macintosh language code, or
+/// an ISO 639-2/T code packed into three 5 bit letters. Codes it can't name
+/// are `und`, like the unspecified language.
+fn language(code: u16) -> String {
+    if code < 0x400 {
+        let language = MAC_LANGUAGES.get(usize::from(code)).copied();
+        return String::from(language.unwrap_or("und"));
+    }
+
+    let letters = [10, 5, 0].map(|shift| (code >> shift & 0x1F) as u8 + 0x60);
+    if letters.iter().all(u8::is_ascii_lowercase) {
+        String::from_utf8_lossy(&letters).into_owned()
+    } else {
+        String::from("und")
+    }
+}
+
+/// The `(language, text)` of each localized version of a text item, like
+/// `©nam`: the `data` atoms of its `ilst` item, whose locale's language is 0
+/// (`und`) for the default one, then the entries of the QuickTime user data
+/// atom of the same name.
+pub(crate) fn localized_texts(data: &[u8], name: &[u8; 4]) -> Vec<(String, String)> {
+    let mut texts = Vec::new();
+
+    let item = ilst(data).and_then(|ilst| child(ilst, name));
+    for data in Atoms::new(item.unwrap_or_default()).filter(|atom| &atom.name == b"data") {
+        // UTF-8 text, then a locale of a country and a language code
+        let code = match (data.data.get(..4), u16_be(data.data, 6)) {
+            (Some([0, 0, 0, 1]), Some(code)) => code,
+            _ => continue,
+        };
+
+        let language = if code == 0 {
+            String::from("und")
+        } else {
+            language(code)
+        };
+        texts.push((
+            language,
+            String::from_utf8_lossy(&data.data[8..]).into_owned(),
+        ));
+    }
+
+    // each entry is a 16 bit size and language code, then the text
+    let mut entries = find(data, &[b"moov", b"udta", name]).unwrap_or_default();
+    while let (Some(size), Some(code)) = (u16_be(entries, 0), u16_be(entries, 2)) {
+        let end = 4 + usize::from(size);
+        let text = match entries.get(4..end) {
+            Some(text) => text,
+            None => break,
+        };
+
+        texts.push((language(code), String::from_utf8_lossy(text).into_owned()));
+        entries = &entries[end..];
+    }
+
+    texts
+}
+
+/// Builds a freeform (`----`) item holding a UTF-8 string.
+fn freeform_item(mean: &str, name: &str, value: &str) -> Option<Vec<u8>> {
+    // type indicator 1 is UTF-8 text, followed by a zero locale
+    let data = atom(
+        b"data",
+        &[&1_u32.to_be_bytes(), &[0; 4], value.as_bytes()].concat(),
+    )?;
+
+    let payload = [
+        full_atom(b"mean", mean.as_bytes())?,
+        full_atom(b"name", name.as_bytes())?,
+        data,
+    ]
+    .concat();
+
+    atom(b"----", &payload)
+}
+
+struct ItemEdit<'a> {
+    mean: &'a str,
+    name: &'a str,
+    item: &'a [u8],
+    /// Shift applied to chunk offsets that point past the end of `moov`.
+    shift: i64,
+    moov_end: u64,
+}
+
+impl ItemEdit<'_> {
+    /// Rewrites the children of `parent`, which is on the way from `moov` to
+    /// the `ilst` that gets the item if `on_path` is set. Off the path, e.g. in
+    /// a track's `udta`, only the chunk offsets are patched.
+    fn rewrite_children(&self, payload: &[u8], parent: &[u8; 4], on_path: bool) -> Option<Vec<u8>> {
+        let next = Self::next_on_path(parent).filter(|_| on_path);
+        let in_ilst = on_path && parent == b"ilst";
+
+        let mut out = Vec::with_capacity(payload.len());
+        let mut found = false;
+
+        for child in Atoms::new(payload) {
+            let child_on_path = Some(&child.name) == next;
+            found |= child_on_path;
+
+            if in_ilst && &child.name == b"----" && is_freeform(child.data, self.mean, self.name) {
+                continue;
+            }
+
+            let rewritten = self.rewrite(&child.name, child.data, child_on_path)?;
+            out.extend(atom(&child.name, &rewritten)?);
+        }
+
+        if in_ilst {
+            out.extend_from_slice(self.item);
+        } else if let (false, Some(name)) = (found, next) {
+            out.extend(atom(name, &self.rewrite(name, &Self::empty(name), true)?)?);
+        }
+
+        Some(out)
+    }
+
+    fn rewrite(&self, name: &[u8; 4], payload: &[u8], on_path: bool) -> Option<Vec<u8>> {
+        match name {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" => {
+                self.rewrite_children(payload, name, on_path)
+            }
+            b"udta" | b"ilst" if on_path => self.rewrite_children(payload, name, on_path),
+            b"meta" if on_path => {
+                // keep the full box header, if there is one
+                let header_len = if payload.get(4..8) == Some(b"hdlr") {
+                    0
+                } else {
+                    4
+                };
+                let (header, children) = payload.split_at(header_len.min(payload.len()));
+
+                Some([header, &self.rewrite_children(children, name, on_path)?].concat())
+            }
+            b"stco" => self.shift_offsets(payload, 4),
+            b"co64" => self.shift_offsets(payload, 8),
+            _ => Some(payload.to_vec()),
+        }
+    }
+
+    /// The container that has to exist under `parent` on the way to `ilst`.
+    fn next_on_path(parent: &[u8; 4]) -> Option<&'static [u8; 4]> {
+        match parent {
+            b"moov" => Some(b"udta"),
+            b"udta" => Some(b"meta"),
+            b"meta" => Some(b"ilst"),
+            _ => None,
+        }
+    }
+
+    /// The payload of a newly created container.
+    fn empty(name: &[u8; 4]) -> Vec<u8> {
+        match name {
+            // full box header, followed by the `mdir` handler iTunes expects
+            b"meta" => [
+                &[0; 4][..],
+                &full_atom(b"hdlr", &[&[0; 4][..], b"mdirappl", &[0; 9]].concat())
+                    .unwrap_or_default(),
+            ]
+            .concat(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn shift_offsets(&self, payload: &[u8], width: usize) -> Option<Vec<u8>> {
+        let mut payload = payload.to_vec();
+
+        // version/flags, then the entry count
+        for entry in payload.get_mut(8..)?.chunks_exact_mut(width) {
+            let offset = match width {
+                4 => u64::from(u32::from_be_bytes(entry.try_into().ok()?)),
+                _ => u64::from_be_bytes(entry.try_into().ok()?),
+            };
+
+            if offset < self.moov_end {
+                continue;
+            }
+
+            let offset = u64::try_from(i64::try_from(offset).ok()? + self.shift).ok()?;
+            match width {
+                4 => entry.copy_from_slice(&u32::try_from(offset).ok()?.to_be_bytes()),
+                _ => entry.copy_from_slice(&offset.to_be_bytes()),
+            }
+        }
+
+        Some(payload)
+    }
+}
+
+/// Rewrites the file with a freeform item set in the iTunes item list, replacing
+/// any item with the same `mean` and `name`. Missing `udta`/`meta`/`ilst`
+/// containers are created, and chunk offsets into `mdat` are fixed up when
+/// `moov` precedes it.
+pub(crate) fn set_freeform(data: &[u8], mean: &str, name: &str, value: &str) -> Option<Vec<u8>> {
+    let item = freeform_item(mean, name, value)?;
+
+    let moov = Atoms::new(data).find(|atom| &atom.name == b"moov")?;
+
+    let mut edit = ItemEdit {
+        mean,
+        name,
+        item: &item,
+        shift: 0,
+        moov_end: moov.range.end as u64,
+    };
+
+    // the first pass only measures how much `moov` grows by
+    let unshifted = atom(b"moov", &edit.rewrite(b"moov", moov.data, true)?)?;
+    edit.shift = unshifted.len() as i64 - moov.range.len() as i64;
+    let rewritten = atom(b"moov", &edit.rewrite(b"moov", moov.data, true)?)?;
+
+    Some(
+        [
+            &data[..moov.range.start],
+            &rewritten,
+            &data[moov.range.end..],
+        ]
+        .concat(),
+    )
+}
+
+fn u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+/// The flags of a full box, from its version/flags header.
+fn flags(data: &[u8]) -> Option<u32> {
+    Some(u32_be(data, 0)? & 0x00FF_FFFF)
+}
+
+/// Whether the samples are in movie fragments (`moof`), as in fMP4 files and
+/// DASH segments, rather than in the `moov` sample tables.
+pub(crate) fn is_fragmented(data: &[u8]) -> bool {
+    Atoms::new(data).any(|atom| &atom.name == b"moof") || find(data, &[b"moov", b"mvex"]).is_some()
+}
+
+/// The duration in seconds of the segments indexed by the top-level `sidx`
+/// boxes of the track `track_id`, or of any track if it's not known.
+pub(crate) fn sidx_seconds(data: &[u8], track_id: Option<u32>) -> Option<f64> {
+    let mut seconds = None;
+
+    for sidx in Atoms::new(data).filter(|atom| &atom.name == b"sidx") {
+        let sidx = sidx.data;
+        if track_id.is_some_and(|track_id| u32_be(sidx, 4) != Some(track_id)) {
+            continue;
+        }
+
+        let timescale = u32_be(sidx, 8).filter(|&timescale| timescale != 0)?;
+        // the earliest presentation time and first offset are 64-bit in version 1
+        let references = if sidx.first()? == &0 { 24 } else { 32 };
+        let count = u16::from_be_bytes(sidx.get(references - 2..references)?.try_into().ok()?);
+
+        let duration = sidx
+            .get(references..)?
+            .chunks_exact(12)
+            .take(count.into())
+            // references to other `sidx` boxes are counted over there
+            .filter(|reference| reference[0] & 0x80 == 0)
+            .map(|reference| u64::from(u32_be(reference, 4).unwrap_or_default()))
+            .sum::<u64>();
+
+        *seconds.get_or_insert(0.0) += duration as f64 / f64::from(timescale);
+    }
+
+    seconds
+}
+
+/// The `trex` default sample duration of the track `track_id`.
+fn trex_default_duration(data: &[u8], track_id: u32) -> Option<u32> {
+    Atoms::new(find(data, &[b"moov", b"mvex"])?)
+        .filter(|atom| &atom.name == b"trex")
+        .find(|trex| u32_be(trex.data, 4) == Some(track_id))
+        .and_then(|trex| u32_be(trex.data, 12))
+}
+
+/// The duration of the track fragment `traf` in its track's timescale, from
+/// its `trun` sample durations, if it belongs to `track_id`.
+fn traf_duration(traf: &[u8], track_id: u32, default_duration: Option<u32>) -> Option<u64> {
+    let tfhd = child(traf, b"tfhd")?;
+    if u32_be(tfhd, 4)? != track_id {
+        return None;
+    }
+
+    let tfhd_flags = flags(tfhd)?;
+    // the default sample duration follows the optional base data offset and
+    // sample description index
+    let default_duration = if tfhd_flags & 0x08 != 0 {
+        let offset = 8
+            + if tfhd_flags & 0x01 != 0 { 8 } else { 0 }
+            + if tfhd_flags & 0x02 != 0 { 4 } else { 0 };
+        u32_be(tfhd, offset)
+    } else {
+        default_duration
+    };
+
+    let mut duration = 0;
+
+    for trun in Atoms::new(traf).filter(|atom| &atom.name == b"trun") {
+        let trun = trun.data;
+        let trun_flags = flags(trun)?;
+        let sample_count = u32_be(trun, 4)?;
+
+        if trun_flags & 0x100 == 0 {
+            duration += u64::from(sample_count) * u64::from(default_duration?);
+            continue;
+        }
+
+        // the optional data offset and first sample flags, then a record per
+        // sample, of each of the duration, size, flags and composition offset
+        // that's present
+        let start = 8
+            + if trun_flags & 0x01 != 0 { 4 } else { 0 }
+            + if trun_flags & 0x04 != 0 { 4 } else { 0 };
+        let record_len = 4 * (trun_flags >> 8 & 0x0F).count_ones() as usize;
+
+        duration += trun
+            .get(start..)?
+            .chunks_exact(record_len)
+            .take(sample_count as usize)
+            .map(|record| u64::from(u32_be(record, 0).unwrap_or_default()))
+            .sum::<u64>();
+    }
+
+    Some(duration)
+}
+
+/// The duration of the track `track_id` in its own timescale, summed over the
+/// sample durations of every movie fragment.
+pub(crate) fn fragments_duration(data: &[u8], track_id: u32) -> Option<u64> {
+    let default_duration = trex_default_duration(data, track_id);
+
+    Atoms::new(data)
+        .filter(|atom| &atom.name == b"moof")
+        .flat_map(|moof| Atoms::new(moof.data).filter(|atom| &atom.name == b"traf"))
+        .filter_map(|traf| traf_duration(traf.data, track_id, default_duration))
+        .reduce(|total, duration| total + duration)
+}
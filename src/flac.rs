@@ -0,0 +1,113 @@
+//! A minimal FLAC frame header reader, for the sample position of the last
+//! frame, since `metaflac` stops at the metadata blocks.
+
+/// CRC-8 with polynomial 0x07, which guards each frame header.
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Decodes the UTF-8-like coded frame or sample number, returning it and the
+/// number of bytes it took.
+fn coded_number(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = match first.leading_ones() {
+        0 => return Some((first.into(), 1)),
+        // a continuation byte can't start a number
+        1 => return None,
+        len @ 2..=7 => len as usize,
+        _ => return None,
+    };
+
+    let value =
+        data.get(1..len)?
+            .iter()
+            .try_fold(u64::from(first & (0x7F >> len)), |value, &byte| {
+                if byte & 0xC0 == 0x80 {
+                    Some(value << 6 | u64::from(byte & 0x3F))
+                } else {
+                    None
+                }
+            })?;
+
+    Some((value, len))
+}
+
+/// Parses the frame header at the start of `data` into the number of the
+/// first sample after the frame, checking its CRC so that a stray sync code
+/// inside a frame isn't mistaken for one.
+fn frame_end(data: &[u8]) -> Option<u64> {
+    let header = data.get(..4)?;
+    // the sync code, then a reserved zero bit and the blocking strategy
+    if header[0] != 0xFF || header[1] & 0xFE != 0xF8 || header[3] & 0x01 != 0 {
+        return None;
+    }
+
+    let variable_block_size = header[1] & 0x01 != 0;
+    let (number, number_len) = coded_number(&data[4..])?;
+    let mut pos = 4 + number_len;
+
+    let block_size = match header[2] >> 4 {
+        0 => return None,
+        1 => 192,
+        code @ 2..=5 => 576 << (code - 2),
+        6 => {
+            pos += 1;
+            u64::from(*data.get(pos - 1)?) + 1
+        }
+        7 => {
+            pos += 2;
+            u64::from(u16::from_be_bytes([
+                *data.get(pos - 2)?,
+                *data.get(pos - 1)?,
+            ])) + 1
+        }
+        code => 256 << (code - 8),
+    };
+
+    pos += match header[2] & 0x0F {
+        12 => 1,
+        13 | 14 => 2,
+        15 => return None,
+        _ => 0,
+    };
+
+    if crc8(data.get(..pos)?) != *data.get(pos)? {
+        return None;
+    }
+
+    // fixed block size streams number their frames, variable ones their samples
+    let first_sample = if variable_block_size {
+        number
+    } else {
+        number.checked_mul(block_size)?
+    };
+
+    first_sample.checked_add(block_size)
+}
+
+/// The number of samples up to the end of the last frame in `data`, for
+/// streams whose STREAMINFO doesn't tell, e.g. while they're being recorded.
+pub(crate) fn samples_written(data: &[u8]) -> Option<u64> {
+    let mut end = data.len();
+
+    while let Some(pos) = data[..end]
+        .windows(2)
+        .rposition(|window| window[0] == 0xFF && window[1] & 0xFE == 0xF8)
+    {
+        if let Some(samples) = frame_end(&data[pos..]) {
+            return Some(samples);
+        }
+
+        end = pos;
+    }
+
+    None
+}
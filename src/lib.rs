@@ -1,290 +1,5577 @@
 use serde::Serialize;
 use serde_with::skip_serializing_none;
-use std::io::Cursor;
+use std::{collections::HashMap, fmt, io::Cursor};
 
 use wasm_bindgen::prelude::*;
 
+mod ape;
+mod atoms;
+mod flac;
+#[cfg(feature = "hash")]
+mod md5;
+mod ogg;
+mod oversized;
+mod picture;
+mod riff;
+mod structure;
+mod surround;
+
+pub use encoding_rs;
+
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(typescript_type = "Metadata | null")]
+    #[wasm_bindgen(typescript_type = "MetadataJson")]
     pub type IMetadata;
+
+    #[wasm_bindgen(typescript_type = "Record<string, string> | undefined")]
+    pub type IExtra;
+
+    #[wasm_bindgen(typescript_type = "string[] | undefined")]
+    pub type IWarnings;
+
+    #[wasm_bindgen(typescript_type = "[number, number] | undefined")]
+    pub type IAudioRange;
+
+    #[wasm_bindgen(typescript_type = "string[] | undefined")]
+    pub type IBrands;
+
+    #[wasm_bindgen(typescript_type = "Array<[number, number]> | null")]
+    pub type ISeekTable;
+
+    #[wasm_bindgen(typescript_type = "Array<[string, Uint8Array]> | null")]
+    pub type IApplicationBlocks;
+
+    #[wasm_bindgen(typescript_type = "Array<[number, string]> | null")]
+    pub type IMarkers;
+
+    #[wasm_bindgen(typescript_type = "BextInfo | null")]
+    pub type IBextInfo;
+
+    #[wasm_bindgen(typescript_type = "Array<[string, string]> | null")]
+    pub type IRawTags;
+
+    #[wasm_bindgen(typescript_type = "Array<[string, string]> | null")]
+    pub type ILocalizedTitles;
+
+    #[wasm_bindgen(typescript_type = "PurchaseInfo | null")]
+    pub type IPurchaseInfo;
+
+    #[wasm_bindgen(typescript_type = "Picture[]")]
+    pub type IPictures;
+
+    #[wasm_bindgen(typescript_type = "Format[]")]
+    pub type IFormats;
+
+    #[wasm_bindgen(typescript_type = "string[]")]
+    pub type IExtensions;
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(callback: &js_sys::Function, millis: i32);
 }
 
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND_CONTENT: &'static str = r#"
-type Format = 'MP3' | 'FLAC' | 'OPUS' | 'AAC' | 'ALAC' | 'AV1' | 'VP8' | 'VP9' | 'WAV';
+type Format = 'MP3' | 'FLAC' | 'OPUS' | 'VORBIS' | 'AAC' | 'ALAC' | 'AV1' | 'VP8' | 'VP9' | 'WAV' | 'AMR' | 'DTS' | 'AC3' | 'UNKNOWN';
 
-type Metadata = {
+type MetadataJson = {
     artist?: string;
     album?: string;
     title?: string;
+    comment?: string;
+    genre?: string;
+    date?: string;
+    disc_subtitle?: string;
+    original_artist?: string;
+    original_album?: string;
+    original_year?: number;
+    encoder?: string;
+    track_number?: number;
+    total_tracks?: number;
+    disc_number?: number;
+    total_discs?: number;
+    podcast?: boolean;
+    podcast_url?: string;
+    episode_guid?: string;
+    podcast_category?: string;
+    gapless?: boolean;
+    media_type?:
+        | 'movie' | 'music' | 'audiobook' | 'bookmark' | 'music_video' | 'short_film'
+        | 'tv_show' | 'booklet' | 'ringtone' | 'podcast' | 'itunes_u';
 
     seconds?: number;
     format: Format;
+    major_brand?: string;
+    compatible_brands?: string[];
     channels?: number;
+    stereo_mode?: 'stereo' | 'joint_stereo' | 'dual_channel' | 'mono';
+    crc_protected?: boolean;
+    emphasis?: 'none' | '50_15_us' | 'reserved' | 'ccitt_j17';
+    channel_mask?: number;
+    channel_layout?: string;
     bitrate?: number;
+    min_bitrate?: number;
+    max_bitrate?: number;
+    effective_bitrate_per_channel?: number;
+    bitrate_mode?: 'cbr' | 'vbr' | 'abr' | 'lossless' | 'unknown';
     bit_depth?: number;
+    valid_bit_depth?: number;
+    sample_format?: 'int' | 'float' | 'adpcm' | 'alaw' | 'mulaw' | 'gsm' | 'mpeg' | 'compressed';
     sample_rate?: number;
+    frame_count?: number;
+    audio_md5?: string;
+    audio_range?: [number, number];
+    sound_check?: number[];
+    volume_adjustment_db?: number;
+    track_gain_db?: number;
+    album_gain_db?: number;
+    output_gain_db?: number;
+    cover_width?: number;
+    cover_height?: number;
+    truncated?: boolean;
+    has_id3v1?: boolean;
+    has_id3v2?: boolean;
+
+    extra?: Record<string, string>;
+    warnings?: string[];
+};
+
+type PictureType =
+    | 'other' | 'icon' | 'other_icon' | 'cover_front' | 'cover_back' | 'leaflet' | 'media'
+    | 'lead_artist' | 'artist' | 'conductor' | 'band' | 'composer' | 'lyricist'
+    | 'recording_location' | 'during_recording' | 'during_performance' | 'screen_capture'
+    | 'bright_fish' | 'illustration' | 'band_logo' | 'publisher_logo';
+
+type Picture = {
+    picture_type: PictureType;
+    mime_type: string;
+    description: string;
+    data: Uint8Array;
+};
+
+type BextInfo = {
+    description?: string;
+    originator?: string;
+    originator_reference?: string;
+    origination_date?: string;
+    origination_time?: string;
+    time_reference: number;
+    time_reference_seconds?: number;
+    version: number;
+    coding_history?: string;
+};
+
+type PurchaseInfo = {
+    account?: string;
+    content_id?: number;
+    purchase_date?: string;
 };
 "#;
 
-#[derive(Serialize)]
+#[derive(Debug)]
+pub enum FazerError {
+    Io(std::io::Error),
+    UnrecognizedFormat,
+    Unsupported(&'static str),
+    Tag(String),
+    /// A strict read couldn't determine these fields.
+    Incomplete(Vec<&'static str>),
+    /// A read with `read_with_timeout` took longer than this.
+    Timeout(std::time::Duration),
+    /// `read_with_timeout` already has as many reads running as
+    /// `set_max_timeout_workers` allows.
+    Busy,
+}
+
+impl fmt::Display for FazerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::UnrecognizedFormat => f.write_str("unrecognized format"),
+            Self::Unsupported(what) => write!(f, "{} is not supported", what),
+            Self::Tag(err) => write!(f, "failed to write tag: {}", err),
+            Self::Incomplete(fields) => {
+                write!(f, "could not determine the {}", fields.join(", "))
+            }
+            Self::Timeout(timeout) => write!(f, "timed out after {:?}", timeout),
+            Self::Busy => f.write_str("too many reads running"),
+        }
+    }
+}
+
+impl std::error::Error for FazerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::UnrecognizedFormat
+            | Self::Unsupported(_)
+            | Self::Tag(_)
+            | Self::Incomplete(_)
+            | Self::Timeout(_)
+            | Self::Busy => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FazerError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// The codec of a file's audio, which is what `Metadata::format` reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "UPPERCASE")]
-enum Format {
+pub enum Format {
     Mp3,
     Flac,
     Opus,
+    Vorbis,
     Aac,
     Alac,
     Av1,
     Vp8,
     Vp9,
     Wav,
+    Amr,
+    Dts,
+    Ac3,
+    /// Audio of a codec none of the readers know.
+    Unknown,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BitrateMode {
+    Cbr,
+    Vbr,
+    Abr,
+    Lossless,
+    Unknown,
+}
+
+impl Format {
+    /// The name it's serialized as.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Mp3 => "MP3",
+            Self::Flac => "FLAC",
+            Self::Opus => "OPUS",
+            Self::Vorbis => "VORBIS",
+            Self::Aac => "AAC",
+            Self::Alac => "ALAC",
+            Self::Av1 => "AV1",
+            Self::Vp8 => "VP8",
+            Self::Vp9 => "VP9",
+            Self::Wav => "WAV",
+            Self::Amr => "AMR",
+            Self::Dts => "DTS",
+            Self::Ac3 => "AC3",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl BitrateMode {
+    /// The name it's serialized as.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Cbr => "cbr",
+            Self::Vbr => "vbr",
+            Self::Abr => "abr",
+            Self::Lossless => "lossless",
+            Self::Unknown => "unknown",
+        }
+    }
 }
 
+#[wasm_bindgen]
 #[skip_serializing_none]
 #[derive(Serialize)]
 pub struct Metadata {
     artist: Option<String>,
     album: Option<String>,
     title: Option<String>,
+    comment: Option<String>,
+    genre: Option<String>,
+    date: Option<String>,
+    disc_subtitle: Option<String>,
+    original_artist: Option<String>,
+    original_album: Option<String>,
+    original_year: Option<i32>,
+    encoder: Option<String>,
+    track_number: Option<u32>,
+    total_tracks: Option<u32>,
+    disc_number: Option<u32>,
+    total_discs: Option<u32>,
+    podcast: Option<bool>,
+    podcast_url: Option<String>,
+    episode_guid: Option<String>,
+    podcast_category: Option<String>,
+    gapless: Option<bool>,
+    media_type: Option<String>,
 
     seconds: Option<f64>,
 
     format: Format,
+    major_brand: Option<String>,
+    compatible_brands: Option<Vec<String>>,
     channels: Option<u32>,
+    stereo_mode: Option<String>,
+    crc_protected: Option<bool>,
+    emphasis: Option<String>,
+    channel_mask: Option<u32>,
+    channel_layout: Option<String>,
     bitrate: Option<f64>,
+    min_bitrate: Option<f64>,
+    max_bitrate: Option<f64>,
+    effective_bitrate_per_channel: Option<f64>,
+    bitrate_mode: Option<BitrateMode>,
     bit_depth: Option<u16>,
+    valid_bit_depth: Option<u16>,
+    sample_format: Option<String>,
     sample_rate: Option<f64>,
+    frame_count: Option<u64>,
+    audio_md5: Option<String>,
+    audio_range: Option<(u64, u64)>,
+    sound_check: Option<Vec<u32>>,
+    volume_adjustment_db: Option<f64>,
+    track_gain_db: Option<f64>,
+    album_gain_db: Option<f64>,
+    output_gain_db: Option<f64>,
+    cover_width: Option<u32>,
+    cover_height: Option<u32>,
+    truncated: Option<bool>,
+    has_id3v1: Option<bool>,
+    has_id3v2: Option<bool>,
+
+    extra: Option<HashMap<String, String>>,
+    warnings: Option<Vec<String>>,
+}
+
+#[wasm_bindgen]
+impl Metadata {
+    #[wasm_bindgen(getter)]
+    pub fn artist(&self) -> Option<String> {
+        self.artist.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn album(&self) -> Option<String> {
+        self.album.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn comment(&self) -> Option<String> {
+        self.comment.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn genre(&self) -> Option<String> {
+        self.genre.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn date(&self) -> Option<String> {
+        self.date.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn disc_subtitle(&self) -> Option<String> {
+        self.disc_subtitle.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn original_artist(&self) -> Option<String> {
+        self.original_artist.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn original_album(&self) -> Option<String> {
+        self.original_album.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn original_year(&self) -> Option<i32> {
+        self.original_year
+    }
+
+    /// The software that encoded the file, from the `ENCODER` comment of FLAC
+    /// and Ogg files, or else the vendor string of a FLAC file's comments.
+    #[wasm_bindgen(getter)]
+    pub fn encoder(&self) -> Option<String> {
+        self.encoder.clone()
+    }
+
+    /// The track's number, from `TRCK`, `TRACKNUMBER` or `trkn`, which may also
+    /// hold the total as in `3/12`.
+    #[wasm_bindgen(getter)]
+    pub fn track_number(&self) -> Option<u32> {
+        self.track_number
+    }
+
+    /// The number of tracks, from the total of the track number, or else
+    /// `TRACKTOTAL` or `TOTALTRACKS`.
+    #[wasm_bindgen(getter)]
+    pub fn total_tracks(&self) -> Option<u32> {
+        self.total_tracks
+    }
+
+    /// The disc's number within a set, from `TPOS`, `DISCNUMBER` or `disk`,
+    /// like `track_number`.
+    #[wasm_bindgen(getter)]
+    pub fn disc_number(&self) -> Option<u32> {
+        self.disc_number
+    }
+
+    /// The number of discs, like `total_tracks`.
+    #[wasm_bindgen(getter)]
+    pub fn total_discs(&self) -> Option<u32> {
+        self.total_discs
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn podcast(&self) -> Option<bool> {
+        self.podcast
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn podcast_url(&self) -> Option<String> {
+        self.podcast_url.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn episode_guid(&self) -> Option<String> {
+        self.episode_guid.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn podcast_category(&self) -> Option<String> {
+        self.podcast_category.clone()
+    }
+
+    /// Whether iTunes marked the track as part of a gapless album, from the
+    /// MP4 `pgap` item or the ID3 `TXXX:iTunPGAP` frame.
+    #[wasm_bindgen(getter)]
+    pub fn gapless(&self) -> Option<bool> {
+        self.gapless
+    }
+
+    /// The iTunes media kind, like `audiobook` or `podcast`.
+    #[wasm_bindgen(getter)]
+    pub fn media_type(&self) -> Option<String> {
+        self.media_type.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seconds(&self) -> Option<f64> {
+        self.seconds
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        String::from(self.format.as_str())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn major_brand(&self) -> Option<String> {
+        self.major_brand.clone()
+    }
+
+    #[wasm_bindgen(getter = compatible_brands)]
+    pub fn compatible_brands_js(&self) -> IBrands {
+        to_js(&self.compatible_brands)
+            .unwrap_or(JsValue::UNDEFINED)
+            .unchecked_into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channels(&self) -> Option<u32> {
+        self.channels
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stereo_mode(&self) -> Option<String> {
+        self.stereo_mode.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn crc_protected(&self) -> Option<bool> {
+        self.crc_protected
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn emphasis(&self) -> Option<String> {
+        self.emphasis.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channel_mask(&self) -> Option<u32> {
+        self.channel_mask
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn channel_layout(&self) -> Option<String> {
+        self.channel_layout.clone()
+    }
+
+    /// The average bitrate in kbps, of 1000 bits per second each, whatever
+    /// unit the format itself stores it in.
+    #[wasm_bindgen(getter)]
+    pub fn bitrate(&self) -> Option<f64> {
+        self.bitrate
+    }
+
+    /// The lowest bitrate of an MP3 file's frames, in kbps.
+    #[wasm_bindgen(getter)]
+    pub fn min_bitrate(&self) -> Option<f64> {
+        self.min_bitrate
+    }
+
+    /// The highest bitrate of an MP3 file's frames, or the peak an MP4 file's
+    /// `esds` declares, in kbps.
+    #[wasm_bindgen(getter)]
+    pub fn max_bitrate(&self) -> Option<f64> {
+        self.max_bitrate
+    }
+
+    /// The average bitrate over every frame, in kbps, split between the
+    /// channels. A stereo or dual-mono MP3 at 128 kbps only gets 64 kbps for
+    /// each channel, the same as a 64 kbps mono one.
+    #[wasm_bindgen(getter)]
+    pub fn effective_bitrate_per_channel(&self) -> Option<f64> {
+        self.effective_bitrate_per_channel
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn bitrate_mode(&self) -> Option<String> {
+        self.bitrate_mode.map(|mode| String::from(mode.as_str()))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn bit_depth(&self) -> Option<u16> {
+        self.bit_depth
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn valid_bit_depth(&self) -> Option<u16> {
+        self.valid_bit_depth
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_format(&self) -> Option<String> {
+        self.sample_format.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+
+    /// The number of MPEG audio frames, as declared by the Xing/Info or VBRI
+    /// header, or else counted. For MP4 files, the number of samples of the
+    /// audio track, each of them a frame of the codec.
+    #[wasm_bindgen(getter)]
+    pub fn frame_count(&self) -> Option<u64> {
+        self.frame_count
+    }
+
+    /// The MD5 of the audio alone, as lowercase hex, which stays the same when
+    /// the tags change. For FLAC it's STREAMINFO's MD5 of the decoded samples,
+    /// and with the `hash` feature, for MP3, MP4, WAV and AMR files it's the MD5
+    /// of the encoded audio between the tags.
+    #[wasm_bindgen(getter)]
+    pub fn audio_md5(&self) -> Option<String> {
+        self.audio_md5.clone()
+    }
+
+    #[wasm_bindgen(getter = audio_range)]
+    pub fn audio_range_js(&self) -> IAudioRange {
+        to_js(&self.audio_range)
+            .unwrap_or(JsValue::UNDEFINED)
+            .unchecked_into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sound_check(&self) -> Option<Vec<u32>> {
+        self.sound_check.clone()
+    }
+
+    /// The gain, in dB, of the ID3 relative volume adjustment frame: the master
+    /// channel of `RVA2`, or the mean of `RVAD`'s left and right channels.
+    #[wasm_bindgen(getter)]
+    pub fn volume_adjustment_db(&self) -> Option<f64> {
+        self.volume_adjustment_db
+    }
+
+    /// The ReplayGain track gain, in dB, from the `REPLAYGAIN_TRACK_GAIN`
+    /// comment, or Opus' `R128_TRACK_GAIN` moved from the -23 LUFS reference of
+    /// EBU R128 to ReplayGain's -18 LUFS. Opus gains apply on top of
+    /// `output_gain_db`.
+    #[wasm_bindgen(getter)]
+    pub fn track_gain_db(&self) -> Option<f64> {
+        self.track_gain_db
+    }
+
+    /// The ReplayGain album gain, in dB, like `track_gain_db`.
+    #[wasm_bindgen(getter)]
+    pub fn album_gain_db(&self) -> Option<f64> {
+        self.album_gain_db
+    }
+
+    /// The gain, in dB, of an Opus file's `OpusHead`, which decoders always
+    /// apply.
+    #[wasm_bindgen(getter)]
+    pub fn output_gain_db(&self) -> Option<f64> {
+        self.output_gain_db
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cover_width(&self) -> Option<u32> {
+        self.cover_width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn cover_height(&self) -> Option<u32> {
+        self.cover_height
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn truncated(&self) -> Option<bool> {
+        self.truncated
+    }
+
+    /// Whether an MP3 file ends with an ID3v1 tag.
+    #[wasm_bindgen(getter)]
+    pub fn has_id3v1(&self) -> Option<bool> {
+        self.has_id3v1
+    }
+
+    /// Whether an MP3 file starts with an ID3v2 tag, even one that fails to parse.
+    #[wasm_bindgen(getter)]
+    pub fn has_id3v2(&self) -> Option<bool> {
+        self.has_id3v2
+    }
+
+    #[wasm_bindgen(getter = extra)]
+    pub fn extra_js(&self) -> IExtra {
+        to_js(&self.extra)
+            .unwrap_or(JsValue::UNDEFINED)
+            .unchecked_into()
+    }
+
+    #[wasm_bindgen(getter = warnings)]
+    pub fn warnings_js(&self) -> IWarnings {
+        to_js(&self.warnings)
+            .unwrap_or(JsValue::UNDEFINED)
+            .unchecked_into()
+    }
+
+    /// A number for picking the best of several copies of a track, where
+    /// higher is better. Lossless files (FLAC, ALAC, and integer or float PCM,
+    /// but not e.g. ADPCM in WAV) score 1,000,000, plus 1,000 per bit of
+    /// depth, plus their sample rate in kHz: 16-bit 44.1 kHz FLAC scores
+    /// 1,016,044. Lossy files score 10 per kbps of bitrate, plus their sample
+    /// rate in kHz: 320 kbps 44.1 kHz MP3 scores 3,244. So any lossless file
+    /// beats any lossy one, and within each, depth or bitrate comes before the
+    /// sample rate. Fractions are rounded down, and a missing sample rate
+    /// counts as zero.
+    ///
+    /// This is `None` when a lossless file has no bit depth, or a lossy one no
+    /// bitrate.
+    #[wasm_bindgen(js_name = qualityScore)]
+    pub fn quality_score(&self) -> Option<u32> {
+        let lossless = matches!(self.bitrate_mode, Some(BitrateMode::Lossless))
+            || matches!(self.format, Format::Flac | Format::Alac)
+            || matches!(self.sample_format.as_deref(), Some("int" | "float"));
+        let sample_rate_khz = self.sample_rate.map_or(0, |rate| (rate / 1_000_f64) as u32);
+
+        let base = if lossless {
+            1_000_000 + 1_000 * u32::from(self.bit_depth?)
+        } else {
+            // keeps lossy scores under the lossless ones, even at absurd bitrates
+            ((self.bitrate? * 10_f64) as u32).min(999_000)
+        };
+
+        Some(base + sample_rate_khz.min(999))
+    }
+
+    /// The metadata as a plain object, as `fazer` used to return it.
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> Result<IMetadata, JsError> {
+        Ok(to_js(self)?.unchecked_into())
+    }
 }
 
 impl Metadata {
+    /// Tags that don't have a field of their own, keyed by their normalized name.
+    pub fn extra(&self) -> Option<&HashMap<String, String>> {
+        self.extra.as_ref()
+    }
+
+    /// The brands of an MP4 file's `ftyp` box, besides its major brand.
+    pub fn compatible_brands(&self) -> Option<&[String]> {
+        self.compatible_brands.as_deref()
+    }
+
+    /// The `(start, end)` byte offsets of the encoded audio within the file,
+    /// between the tags, as `audio_md5` hashes it. For FLAC it's the frames
+    /// after the metadata blocks, and for MP4 it runs from the first
+    /// `mdat` atom's payload to the end of the last's, so it takes in the
+    /// `moof` atoms between those of fragmented files. Ogg files, whose pages
+    /// interleave the audio with their headers, don't have one.
+    pub fn audio_range(&self) -> Option<(u64, u64)> {
+        self.audio_range
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        self.warnings.as_deref().unwrap_or_default()
+    }
+
+    /// Fills every field that's `None` from `other`, e.g. the embedded tags from
+    /// an external database lookup, leaving the fields that are set alone. The
+    /// format stays `self`'s, and so do `extra` tags both have, while the
+    /// warnings of both are kept.
+    pub fn merge(&mut self, other: &Metadata) {
+        self.merge_with(other, false);
+    }
+
+    /// Like `merge`, but if `prefer_other` is set, `other`'s fields (and
+    /// `extra` tags) replace `self`'s wherever both have one.
+    pub fn merge_with(&mut self, other: &Metadata, prefer_other: bool) {
+        fn fill<T: Clone>(field: &mut Option<T>, other: &Option<T>, prefer_other: bool) {
+            if other.is_some() && (prefer_other || field.is_none()) {
+                field.clone_from(other);
+            }
+        }
+
+        // destructured, so that a new field can't be forgotten here
+        let Metadata {
+            artist,
+            album,
+            title,
+            comment,
+            genre,
+            date,
+            disc_subtitle,
+            original_artist,
+            original_album,
+            original_year,
+            encoder,
+            track_number,
+            total_tracks,
+            disc_number,
+            total_discs,
+            podcast,
+            podcast_url,
+            episode_guid,
+            podcast_category,
+            gapless,
+            media_type,
+            seconds,
+            format: _,
+            major_brand,
+            compatible_brands,
+            channels,
+            stereo_mode,
+            crc_protected,
+            emphasis,
+            channel_mask,
+            channel_layout,
+            bitrate,
+            min_bitrate,
+            max_bitrate,
+            effective_bitrate_per_channel,
+            bitrate_mode,
+            bit_depth,
+            valid_bit_depth,
+            sample_format,
+            sample_rate,
+            frame_count,
+            audio_md5,
+            audio_range,
+            sound_check,
+            volume_adjustment_db,
+            track_gain_db,
+            album_gain_db,
+            output_gain_db,
+            cover_width,
+            cover_height,
+            truncated,
+            has_id3v1,
+            has_id3v2,
+            extra,
+            warnings,
+        } = other;
+
+        fill(&mut self.artist, artist, prefer_other);
+        fill(&mut self.album, album, prefer_other);
+        fill(&mut self.title, title, prefer_other);
+        fill(&mut self.comment, comment, prefer_other);
+        fill(&mut self.genre, genre, prefer_other);
+        fill(&mut self.date, date, prefer_other);
+        fill(&mut self.disc_subtitle, disc_subtitle, prefer_other);
+        fill(&mut self.original_artist, original_artist, prefer_other);
+        fill(&mut self.original_album, original_album, prefer_other);
+        fill(&mut self.original_year, original_year, prefer_other);
+        fill(&mut self.encoder, encoder, prefer_other);
+        fill(&mut self.track_number, track_number, prefer_other);
+        fill(&mut self.total_tracks, total_tracks, prefer_other);
+        fill(&mut self.disc_number, disc_number, prefer_other);
+        fill(&mut self.total_discs, total_discs, prefer_other);
+        fill(&mut self.podcast, podcast, prefer_other);
+        fill(&mut self.podcast_url, podcast_url, prefer_other);
+        fill(&mut self.episode_guid, episode_guid, prefer_other);
+        fill(&mut self.podcast_category, podcast_category, prefer_other);
+        fill(&mut self.gapless, gapless, prefer_other);
+        fill(&mut self.media_type, media_type, prefer_other);
+        fill(&mut self.seconds, seconds, prefer_other);
+        fill(&mut self.major_brand, major_brand, prefer_other);
+        fill(&mut self.compatible_brands, compatible_brands, prefer_other);
+        fill(&mut self.channels, channels, prefer_other);
+        fill(&mut self.stereo_mode, stereo_mode, prefer_other);
+        fill(&mut self.crc_protected, crc_protected, prefer_other);
+        fill(&mut self.emphasis, emphasis, prefer_other);
+        fill(&mut self.channel_mask, channel_mask, prefer_other);
+        fill(&mut self.channel_layout, channel_layout, prefer_other);
+        fill(&mut self.bitrate, bitrate, prefer_other);
+        fill(&mut self.min_bitrate, min_bitrate, prefer_other);
+        fill(&mut self.max_bitrate, max_bitrate, prefer_other);
+        fill(
+            &mut self.effective_bitrate_per_channel,
+            effective_bitrate_per_channel,
+            prefer_other,
+        );
+        fill(&mut self.bitrate_mode, bitrate_mode, prefer_other);
+        fill(&mut self.bit_depth, bit_depth, prefer_other);
+        fill(&mut self.valid_bit_depth, valid_bit_depth, prefer_other);
+        fill(&mut self.sample_format, sample_format, prefer_other);
+        fill(&mut self.sample_rate, sample_rate, prefer_other);
+        fill(&mut self.frame_count, frame_count, prefer_other);
+        fill(&mut self.audio_md5, audio_md5, prefer_other);
+        fill(&mut self.audio_range, audio_range, prefer_other);
+        fill(&mut self.sound_check, sound_check, prefer_other);
+        fill(
+            &mut self.volume_adjustment_db,
+            volume_adjustment_db,
+            prefer_other,
+        );
+        fill(&mut self.track_gain_db, track_gain_db, prefer_other);
+        fill(&mut self.album_gain_db, album_gain_db, prefer_other);
+        fill(&mut self.output_gain_db, output_gain_db, prefer_other);
+        fill(&mut self.cover_width, cover_width, prefer_other);
+        fill(&mut self.cover_height, cover_height, prefer_other);
+        fill(&mut self.truncated, truncated, prefer_other);
+        fill(&mut self.has_id3v1, has_id3v1, prefer_other);
+        fill(&mut self.has_id3v2, has_id3v2, prefer_other);
+
+        if let Some(other_extra) = extra {
+            let extra = self.extra.get_or_insert_with(HashMap::new);
+
+            for (key, value) in other_extra {
+                if prefer_other || !extra.contains_key(key) {
+                    extra.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        for warning in warnings.iter().flatten() {
+            self.warn(warning.clone());
+        }
+    }
+
+    /// Drops everything but the fields `read_stream_info` promises.
+    fn into_stream_info(self) -> Self {
+        Self {
+            seconds: self.seconds,
+            channels: self.channels,
+            bitrate: self.bitrate,
+            bit_depth: self.bit_depth,
+            sample_rate: self.sample_rate,
+            ..Self::empty(self.format)
+        }
+    }
+
     fn empty(format: Format) -> Self {
         Self {
             artist: None,
             album: None,
             title: None,
+            comment: None,
+            genre: None,
+            date: None,
+            disc_subtitle: None,
+            original_artist: None,
+            original_album: None,
+            original_year: None,
+            encoder: None,
+            track_number: None,
+            total_tracks: None,
+            disc_number: None,
+            total_discs: None,
+            podcast: None,
+            podcast_url: None,
+            episode_guid: None,
+            podcast_category: None,
+            gapless: None,
+            media_type: None,
             seconds: None,
             format,
+            major_brand: None,
+            compatible_brands: None,
             channels: None,
+            stereo_mode: None,
+            crc_protected: None,
+            emphasis: None,
+            channel_mask: None,
+            channel_layout: None,
             bitrate: None,
+            min_bitrate: None,
+            max_bitrate: None,
+            effective_bitrate_per_channel: None,
+            bitrate_mode: None,
             bit_depth: None,
+            valid_bit_depth: None,
+            sample_format: None,
             sample_rate: None,
+            frame_count: None,
+            audio_md5: None,
+            audio_range: None,
+            sound_check: None,
+            volume_adjustment_db: None,
+            track_gain_db: None,
+            album_gain_db: None,
+            output_gain_db: None,
+            cover_width: None,
+            cover_height: None,
+            truncated: None,
+            has_id3v1: None,
+            has_id3v2: None,
+            extra: None,
+            warnings: None,
         }
     }
-}
 
-pub fn read_mp3(reader: &[u8]) -> Option<Metadata> {
-    let mut metadata = Metadata::empty(Format::Mp3);
+    fn warn(&mut self, warning: impl Into<String>) {
+        self.warnings
+            .get_or_insert_with(Vec::new)
+            .push(warning.into());
+    }
 
-    if let Ok(res) = id3::Tag::read_from(reader) {
-        use id3::TagLike;
+    /// Puts the tag text into Unicode Normalization Form C, composing the
+    /// accents that macOS taggers tend to leave decomposed.
+    fn normalize_unicode(&mut self) {
+        use unicode_normalization::UnicodeNormalization;
 
-        if let Some(artist) = res.artist() {
-            metadata.artist = Some(String::from(artist))
-        } else if let Some(artist) = res.album_artist() {
-            metadata.artist = Some(String::from(artist))
-        }
+        let fields = [
+            &mut self.artist,
+            &mut self.album,
+            &mut self.title,
+            &mut self.comment,
+            &mut self.genre,
+            &mut self.disc_subtitle,
+            &mut self.original_artist,
+            &mut self.original_album,
+            &mut self.encoder,
+            &mut self.podcast_category,
+        ];
+        let extra = self.extra.iter_mut().flat_map(HashMap::values_mut);
 
-        if let Some(album) = res.album() {
-            metadata.album = Some(String::from(album))
+        for text in IntoIterator::into_iter(fields).flatten().chain(extra) {
+            *text = text.nfc().collect();
         }
+    }
 
-        if let Some(title) = res.title() {
-            metadata.title = Some(String::from(title))
+    fn round_seconds(&mut self, precision: Option<u8>) {
+        if let (Some(seconds), Some(precision)) = (&mut self.seconds, precision) {
+            let scale = 10_f64.powi(i32::from(precision));
+            *seconds = (*seconds * scale).round() / scale;
         }
+    }
 
-        metadata.seconds = res
-            .duration()
-            .map(|miliseconds| f64::from(miliseconds) / 1_000_f64)
+    fn set_extra(&mut self, extra: HashMap<String, String>) {
+        self.extra = Some(extra).filter(|extra| !extra.is_empty());
     }
 
-    if let Ok(res) = mp3_metadata::read_from_slice(reader) {
-        if let Some(frame) = res.frames.first() {
-            metadata.channels = match frame.chan_type {
-                mp3_metadata::ChannelType::SingleChannel => Some(1),
-                mp3_metadata::ChannelType::Unknown => None,
-                _ => Some(2),
-            };
+    /// Fills in the text fields from Vorbis comments, as found in FLAC and Ogg.
+    fn set_vorbis_comments(&mut self, comments: &HashMap<String, Vec<String>>) {
+        let get = |key: &str| comments.get(key).and_then(|values| values.first()).cloned();
+
+        self.artist = get("ARTIST").or_else(|| get("ALBUMARTIST"));
+        self.album = get("ALBUM");
+        self.title = get("TITLE");
+        self.comment = get("COMMENT");
+        self.genre = get("GENRE");
+        self.date = get("DATE");
+        self.disc_subtitle = get("DISCSUBTITLE");
+        self.original_artist = get("ORIGINALARTIST");
+        self.original_album = get("ORIGINALALBUM");
+        self.original_year = get("ORIGINALYEAR")
+            .or_else(|| get("ORIGINALDATE"))
+            .as_deref()
+            .and_then(parse_year);
+        self.encoder = get("ENCODER");
 
-            metadata.bitrate = Some(frame.bitrate.into());
-            metadata.sample_rate = Some(frame.sampling_freq.into());
+        // taggers disagree on where the total goes: after the number, as in
+        // `3/12`, or under either of two keys of its own, which some of them
+        // leave empty when writing the other
+        let position = |number: &str, totals: [&str; 2]| {
+            let (number, total) = get(number).map_or((None, None), |value| parse_position(&value));
+            let total = total.or_else(|| {
+                totals
+                    .iter()
+                    .find_map(|&key| get(key).and_then(|value| parse_position(&value).0))
+            });
+
+            (number, total)
         };
+        (self.track_number, self.total_tracks) =
+            position("TRACKNUMBER", ["TRACKTOTAL", "TOTALTRACKS"]);
+        (self.disc_number, self.total_discs) = position("DISCNUMBER", ["DISCTOTAL", "TOTALDISCS"]);
 
-        for tag in res.optional_info {
-            if metadata.title.is_none() && tag.title.is_some() {
-                metadata.title = Some(tag.title.unwrap().clone())
-            }
+        let gain = |replaygain: &str, r128: &str| {
+            get(r128)
+                .and_then(|gain| parse_r128_gain(&gain))
+                .or_else(|| get(replaygain).and_then(|gain| parse_replaygain(&gain)))
+        };
+        self.track_gain_db = gain("REPLAYGAIN_TRACK_GAIN", "R128_TRACK_GAIN");
+        self.album_gain_db = gain("REPLAYGAIN_ALBUM_GAIN", "R128_ALBUM_GAIN");
 
-            if metadata.artist.is_none() && !tag.original_artists.is_empty() {
-                metadata.artist = Some(tag.original_artists.join(", "))
-            }
+        self.set_extra(vorbis_extra(comments));
+    }
+
+    /// Warns if the cover is too large for the picture exports to extract.
+    fn check_cover_size(&mut self, len: Option<usize>) {
+        if let Some(len) = len.filter(|&len| len > DEFAULT_MAX_PICTURE_BYTES) {
+            self.warn(format!(
+                "the {} byte cover is larger than the {} byte picture limit",
+                len, DEFAULT_MAX_PICTURE_BYTES
+            ));
         }
+    }
 
-        if let Some(ref tag) = res.tag {
-            if metadata.artist.is_none() && !tag.artist.is_empty() {
-                metadata.artist = Some(String::from(tag.artist.trim_end_matches('\x00')))
-            }
+    fn set_cover_dimensions(&mut self, dimensions: Option<(u32, u32)>) {
+        if let Some((width, height)) = dimensions {
+            self.cover_width = Some(width);
+            self.cover_height = Some(height);
+        }
+    }
 
-            if metadata.album.is_none() && !tag.album.is_empty() {
-                metadata.album = Some(String::from(tag.album.trim_end_matches('\x00')))
-            }
+    fn set_truncated(&mut self, truncated: bool) {
+        self.truncated = Some(truncated);
 
-            if metadata.title.is_none() && !tag.title.is_empty() {
-                metadata.title = Some(String::from(tag.title.trim_end_matches('\x00')))
-            }
+        if truncated {
+            self.warn("file is shorter than its headers declare, and is probably truncated");
         }
+    }
+}
 
-        metadata.seconds = Some(res.duration.as_secs_f64());
+impl Default for Metadata {
+    /// Metadata with no fields set, of an `Unknown` format.
+    fn default() -> Self {
+        Self::empty(Format::Unknown)
     }
+}
 
-    Some(metadata)
+/// Sets a `Metadata` field for each field name, taking anything that converts
+/// into its type.
+macro_rules! builder_setters {
+    ($($field:ident: $ty:ty,)*) => {
+        $(
+            pub fn $field(mut self, $field: impl Into<$ty>) -> Self {
+                self.metadata.$field = Some($field.into());
+                self
+            }
+        )*
+    };
 }
 
-pub fn read_flac(reader: &[u8]) -> Option<Metadata> {
-    use metaflac::{Block, Tag};
+/// Builds `Metadata` from scratch, e.g. for tests or from an external source,
+/// rather than reading it from a file.
+#[derive(Default)]
+pub struct MetadataBuilder {
+    metadata: Metadata,
+}
 
-    fn get_comment(data: Option<&Vec<String>>) -> Option<&String> {
-        data?.first()
+impl MetadataBuilder {
+    pub fn new(format: Format) -> Self {
+        Self {
+            metadata: Metadata::empty(format),
+        }
     }
 
-    let tag = Tag::read_from(&mut { reader }).ok()?;
-
-    let mut metadata = Metadata::empty(Format::Flac);
+    pub fn format(mut self, format: Format) -> Self {
+        self.metadata.format = format;
+        self
+    }
 
-    for block in tag.blocks() {
-        if let Block::StreamInfo(stream_info) = block {
-            metadata.seconds =
-                Some(stream_info.total_samples as f64 / f64::from(stream_info.sample_rate));
-            metadata.channels = Some(stream_info.num_channels.into());
-        } else if let Block::VorbisComment(comment) = block {
-            if let Some(artist) = get_comment(comment.artist()) {
-                metadata.artist = Some(artist.clone())
-            } else if let Some(artist) = get_comment(comment.album_artist()) {
-                metadata.artist = Some(artist.clone())
-            }
+    builder_setters! {
+        artist: String,
+        album: String,
+        title: String,
+        comment: String,
+        genre: String,
+        date: String,
+        disc_subtitle: String,
+        original_artist: String,
+        original_album: String,
+        original_year: i32,
+        encoder: String,
+        track_number: u32,
+        total_tracks: u32,
+        disc_number: u32,
+        total_discs: u32,
+        podcast: bool,
+        podcast_url: String,
+        episode_guid: String,
+        podcast_category: String,
+        gapless: bool,
+        media_type: String,
+        seconds: f64,
+        major_brand: String,
+        compatible_brands: Vec<String>,
+        channels: u32,
+        stereo_mode: String,
+        crc_protected: bool,
+        emphasis: String,
+        channel_mask: u32,
+        channel_layout: String,
+        bitrate: f64,
+        min_bitrate: f64,
+        max_bitrate: f64,
+        effective_bitrate_per_channel: f64,
+        bit_depth: u16,
+        valid_bit_depth: u16,
+        sample_format: String,
+        sample_rate: f64,
+        frame_count: u64,
+        audio_md5: String,
+        audio_range: (u64, u64),
+        sound_check: Vec<u32>,
+        volume_adjustment_db: f64,
+        track_gain_db: f64,
+        album_gain_db: f64,
+        output_gain_db: f64,
+        cover_width: u32,
+        cover_height: u32,
+        truncated: bool,
+        has_id3v1: bool,
+        has_id3v2: bool,
+    }
 
-            if let Some(album) = get_comment(comment.album()) {
-                metadata.album = Some(album.clone())
-            }
+    /// Adds a tag that doesn't have a field of its own.
+    pub fn extra(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata
+            .extra
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
 
-            if let Some(title) = get_comment(comment.title()) {
-                metadata.title = Some(title.clone())
-            }
-        }
+    pub fn warning(mut self, warning: impl Into<String>) -> Self {
+        self.metadata.warn(warning);
+        self
     }
 
-    Some(metadata)
+    pub fn build(self) -> Metadata {
+        self.metadata
+    }
 }
 
-pub fn read_ogg(reader: &[u8]) -> Option<Metadata> {
-    use ogg_metadata::{read_format, AudioMetadata, OggFormat};
+/// ID3 text frames that aren't mapped to a `Metadata` field of their own, and
+/// the `extra` key they're reported under.
+const ID3_EXTRA_FRAMES: &[(&str, &str)] = &[
+    ("TBPM", "bpm"),
+    ("TCOM", "composer"),
+    ("TCOP", "copyright"),
+    ("TENC", "encodedby"),
+    ("TEXT", "lyricist"),
+    ("TIT1", "grouping"),
+    ("TIT3", "subtitle"),
+    ("TKEY", "initialkey"),
+    ("TLAN", "language"),
+    ("TMED", "media"),
+    ("TMOO", "mood"),
+    ("TPE2", "albumartist"),
+    ("TPE3", "conductor"),
+    ("TPE4", "remixer"),
+    ("TPUB", "label"),
+    ("TSOA", "albumsort"),
+    ("TSOP", "artistsort"),
+    ("TSOT", "titlesort"),
+    ("TSRC", "isrc"),
+    ("TSSE", "encoder"),
+];
 
-    fn format_metadata<T: AudioMetadata>(metadata: &T) -> Metadata {
-        Metadata {
-            channels: Some(metadata.get_output_channel_count().into()),
-            seconds: metadata
-                .get_duration()
-                .map(|duration| duration.as_secs_f64()),
-            ..Metadata::empty(Format::Opus)
-        }
-    }
+/// `TXXX` descriptions that are mapped to a `Metadata` field of their own.
+const ID3_MAPPED_EXTENDED_TEXTS: &[&str] = &["iTunNORM", "iTunPGAP", "ITUNESMEDIATYPE"];
 
-    read_format(Cursor::new(reader)).ok().and_then(|formats| {
-        formats.iter().find_map(|format| match format {
-            OggFormat::Opus(res) => Some(format_metadata(res)),
-            OggFormat::Vorbis(res) => Some(format_metadata(res)),
-            _ => None,
-        })
-    })
+/// Vorbis comment keys that are mapped to a `Metadata` field of their own.
+const VORBIS_MAPPED_KEYS: &[&str] = &[
+    "ARTIST",
+    "ALBUM",
+    "TITLE",
+    "COMMENT",
+    "GENRE",
+    "DATE",
+    "DISCSUBTITLE",
+    "ORIGINALARTIST",
+    "ORIGINALALBUM",
+    "ORIGINALYEAR",
+    "ENCODER",
+    "TRACKNUMBER",
+    "TRACKTOTAL",
+    "TOTALTRACKS",
+    "DISCNUMBER",
+    "DISCTOTAL",
+    "TOTALDISCS",
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "R128_TRACK_GAIN",
+    "R128_ALBUM_GAIN",
+];
+
+/// `INFO` chunk fields that aren't mapped to a `Metadata` field of their own,
+/// and the `extra` key they're reported under.
+const RIFF_INFO_EXTRA_FIELDS: &[(&[u8; 4], &str)] = &[
+    (b"ICOP", "copyright"),
+    (b"IENG", "engineer"),
+    (b"ISFT", "encoder"),
+];
+
+/// The `(start, end)` byte offsets of `region`, a slice of `data`, within it.
+fn region_range(data: &[u8], region: &[u8]) -> (u64, u64) {
+    let start = region.as_ptr() as usize - data.as_ptr() as usize;
+    (start as u64, (start + region.len()) as u64)
 }
 
-pub fn read_mp4(reader: &[u8]) -> Option<Metadata> {
-    use mp4parse::{
-        read_mp4, AudioSampleEntry, CodecType, SampleDescriptionBox, SampleEntry, Track, TrackType,
-    };
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The MD5 of the regions of a file that hold its audio, in order, or `None`
+/// without the `hash` feature, as hashing takes a pass over the whole file.
+fn audio_md5<'a>(regions: impl IntoIterator<Item = &'a [u8]>) -> Option<String> {
+    #[cfg(feature = "hash")]
+    {
+        let mut md5 = md5::Md5::new();
+        for region in regions {
+            md5.update(region);
+        }
+
+        Some(hex(&md5.finish()))
+    }
+
+    #[cfg(not(feature = "hash"))]
+    {
+        drop(regions);
+        None
+    }
+}
+
+/// Lowercases a tag key and drops separators, so that e.g. `ALBUM ARTIST`,
+/// `album_artist` and `AlbumArtist` are all reported as `albumartist`.
+fn normalize_tag_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The free-form comment, as opposed to the ones with a description that
+/// taggers use to stash their own data (e.g. `iTunNORM`).
+fn id3_comment(tag: &id3::Tag) -> Option<&str> {
+    tag.comments()
+        .find(|comment| comment.description.is_empty())
+        .map(|comment| comment.text.as_str())
+}
+
+/// The `TCON` genre, with ID3v1 genre numbers like `(17)` resolved to names.
+fn id3_genre(tag: &id3::Tag) -> Option<String> {
+    use id3::TagLike;
+
+    tag.genre_parsed().map(String::from)
+}
+
+/// The recording date, from ID3v2.4's `TDRC` or ID3v2.3's `TYER`.
+fn id3_date(tag: &id3::Tag) -> Option<&str> {
+    use id3::TagLike;
+
+    tag.get("TDRC")
+        .or_else(|| tag.get("TYER"))
+        .and_then(|frame| frame.content().text())
+}
+
+/// The value of a text frame.
+fn id3_text<'a>(tag: &'a id3::Tag, id: &str) -> Option<&'a str> {
+    use id3::TagLike;
+
+    tag.get(id).and_then(|frame| frame.content().text())
+}
+
+/// The original artists, from the values of `TOPE`.
+fn id3_original_artist(tag: &id3::Tag) -> Option<String> {
+    use id3::TagLike;
+
+    tag.get("TOPE")
+        .and_then(|frame| frame.content().text_values())
+        .and_then(join_artists)
+}
 
-    let ctx = read_mp4(&mut { reader }).ok()?;
+/// Joins artists with `, `, trimming each and dropping empty ones and repeats,
+/// regardless of case.
+fn join_artists<'a>(artists: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut joined: Vec<&str> = Vec::new();
 
-    ctx.tracks
+    for artist in artists.into_iter().map(str::trim) {
+        let repeat = joined
+            .iter()
+            .any(|other| other.to_lowercase() == artist.to_lowercase());
+        if !artist.is_empty() && !repeat {
+            joined.push(artist);
+        }
+    }
+
+    Some(joined.join(", ")).filter(|joined| !joined.is_empty())
+}
+
+/// The disc's subtitle within a set, from `TSST`.
+fn id3_disc_subtitle(tag: &id3::Tag) -> Option<&str> {
+    id3_text(tag, "TSST")
+}
+
+/// The original release year, from ID3v2.3's `TORY` or ID3v2.4's `TDOR`.
+fn id3_original_year(tag: &id3::Tag) -> Option<i32> {
+    id3_text(tag, "TORY")
+        .or_else(|| id3_text(tag, "TDOR"))
+        .and_then(parse_year)
+}
+
+/// Parses a date of any precision, from a year to a time to the second, into
+/// the same `1977-06-10T08:00:00` form as ID3v2.4's `TDRC`. A trailing time
+/// zone, as iTunes writes in MP4's `©day` (e.g. `Z` or `+02:00`), is dropped
+/// along with anything else after the seconds. Dates it can't make out are
+/// kept as they are.
+fn parse_date(date: &str) -> String {
+    match date.trim().parse::<id3::Timestamp>() {
+        Ok(timestamp) => timestamp.to_string(),
+        Err(_) => String::from(date),
+    }
+}
+
+/// The year at the start of a date like `1977` or `1977-06-10`.
+fn parse_year(date: &str) -> Option<i32> {
+    date.trim().get(..4)?.parse().ok()
+}
+
+fn id3_extra(tag: &id3::Tag) -> HashMap<String, String> {
+    let mut extra = HashMap::new();
+
+    for frame in tag.frames() {
+        let name = match ID3_EXTRA_FRAMES.iter().find(|(id, _)| *id == frame.id()) {
+            Some((_, name)) => name,
+            None => continue,
+        };
+
+        if let Some(values) = frame.content().text_values() {
+            extra
+                .entry(String::from(*name))
+                .or_insert_with(|| values.collect::<Vec<_>>().join(", "));
+        }
+    }
+
+    for text in tag.extended_texts() {
+        if !ID3_MAPPED_EXTENDED_TEXTS.contains(&text.description.as_str()) {
+            extra
+                .entry(normalize_tag_key(&text.description))
+                .or_insert_with(|| text.value.clone());
+        }
+    }
+
+    extra
+}
+
+fn vorbis_extra(comments: &HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    comments
         .iter()
-        .filter(|Track { track_type, .. }| track_type == &TrackType::Audio)
-        .filter_map(|track @ Track { stsd, .. }| stsd.as_ref().map(|stsd| (track, stsd)))
-        .find_map(|(track, SampleDescriptionBox { descriptions, .. })| {
-            descriptions.iter().find_map(|entry| match entry {
-                SampleEntry::Audio(entry) => Some((track, entry)),
+        .filter(|(key, _)| !VORBIS_MAPPED_KEYS.contains(&key.to_ascii_uppercase().as_str()))
+        .map(|(key, values)| (normalize_tag_key(key), values.join(", ")))
+        .collect()
+}
+
+/// Parses a track or disc position, like `3`, `03` or `3/12`, into its number
+/// and the total.
+fn parse_position(value: &str) -> (Option<u32>, Option<u32>) {
+    // `id3` takes the slash of ID3v2.3 text for a separator between values, and
+    // hands it over as a null
+    let mut parts = value
+        .splitn(2, &['/', '\0'][..])
+        .map(|part| part.trim().parse().ok().filter(|&number: &u32| number > 0));
+
+    (parts.next().flatten(), parts.next().flatten())
+}
+
+/// Reads an MP4 `trkn` or `disk` item, which is a padded pair of big-endian
+/// numbers, or text like `3/12` from some taggers.
+fn mp4_position(value: &[u8]) -> (Option<u32>, Option<u32>) {
+    if let Some(text) = std::str::from_utf8(value)
+        .ok()
+        .filter(|text| text.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        return parse_position(text);
+    }
+
+    let number = |offset: usize| {
+        value
+            .get(offset..offset + 2)
+            .map(|number| u32::from(u16::from_be_bytes([number[0], number[1]])))
+            .filter(|&number| number > 0)
+    };
+
+    (number(2), number(4))
+}
+
+/// Parses a ReplayGain gain, like `-6.48 dB`.
+fn parse_replaygain(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|gain: &f64| gain.is_finite())
+}
+
+/// Parses an Opus R128 gain, a Q7.8 fixed-point number of dB relative to
+/// -23 LUFS, into a ReplayGain one, relative to -18 LUFS.
+fn parse_r128_gain(value: &str) -> Option<f64> {
+    let gain = value.trim().parse::<i16>().ok()?;
+    Some(f64::from(gain) / 256.0 + 5.0)
+}
+
+/// Names an iTunes media kind, as stored in the MP4 `stik` item.
+fn media_type_name(stik: u8) -> Option<&'static str> {
+    let name = match stik {
+        0 => "movie",
+        1 => "music",
+        2 => "audiobook",
+        5 => "bookmark",
+        6 => "music_video",
+        9 => "short_film",
+        10 => "tv_show",
+        11 => "booklet",
+        14 => "ringtone",
+        21 => "podcast",
+        23 => "itunes_u",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Parses the `ITUNESMEDIATYPE` value taggers like Mp3tag write, either the
+/// name iTunes shows (e.g. `Music Video`) or the `stik` number.
+fn parse_media_type(value: &str) -> Option<&'static str> {
+    if let Ok(stik) = value.trim().parse() {
+        return media_type_name(stik);
+    }
+
+    let name = match normalize_tag_key(value.trim()).as_str() {
+        "movie" => "movie",
+        "normal" | "music" => "music",
+        "audiobook" => "audiobook",
+        "bookmark" => "bookmark",
+        "musicvideo" => "music_video",
+        "shortfilm" => "short_film",
+        "tvshow" => "tv_show",
+        "booklet" => "booklet",
+        "ringtone" => "ringtone",
+        "podcast" => "podcast",
+        "itunesu" => "itunes_u",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Parses Apple's Sound Check (`iTunNORM`) value, ten space-separated hex words.
+fn parse_sound_check(value: &str) -> Option<Vec<u32>> {
+    let values = value
+        .split_whitespace()
+        .map(|word| u32::from_str_radix(word, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if values.len() == 10 {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+/// Parses the master volume channel of an ID3v2.4 `RVA2` frame: an
+/// identification string, then per channel a type byte (1 for the master
+/// volume), a signed 16-bit gain in 1/512 dB, and a peak as wide as the number
+/// of bits in the byte in front of it.
+fn parse_rva2(data: &[u8]) -> Option<f64> {
+    let mut channels = &data[data.iter().position(|&byte| byte == 0)? + 1..];
+
+    while let [channel, gain_high, gain_low, peak_bits, rest @ ..] = channels {
+        if *channel == 1 {
+            let gain = i16::from_be_bytes([*gain_high, *gain_low]);
+            return Some(f64::from(gain) / 512.0);
+        }
+
+        channels = rest.get(usize::from(*peak_bits).div_ceil(8)..)?;
+    }
+
+    None
+}
+
+/// Parses an ID3v2.3 `RVAD` frame: a byte of increment flags (bit 0 for the
+/// right channel, bit 1 for the left), the width in bits of the values, then
+/// the right and left relative volume changes. The spec leaves their unit open,
+/// so they're read as a change in amplitude of `1 ± value / 2^bits`.
+fn parse_rvad(data: &[u8]) -> Option<f64> {
+    let (flags, bits) = (*data.first()?, *data.get(1)?);
+    let len = usize::from(bits).div_ceil(8);
+    if len == 0 || len > 8 {
+        return None;
+    }
+
+    let scale = 2_f64.powi(i32::from(bits));
+    let channel = |index: usize| {
+        let value = data
+            .get(2 + index * len..2 + (index + 1) * len)?
+            .iter()
+            .fold(0_f64, |value, &byte| value * 256.0 + f64::from(byte));
+
+        let ratio = if flags >> index & 1 != 0 {
+            1.0 + value / scale
+        } else {
+            1.0 - value / scale
+        };
+
+        if ratio > 0.0 {
+            Some(20.0 * ratio.log10())
+        } else {
+            None
+        }
+    };
+
+    Some((channel(0)? + channel(1)?) / 2.0)
+}
+
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    /// Use the album artist as `artist` when the track artist is missing.
+    pub artist_from_album_artist_fallback: bool,
+    /// Use the album artist as `artist` even when a track artist is present,
+    /// falling back to the track artist.
+    pub prefer_album_artist: bool,
+    /// Round `seconds` to this many decimal places, so that it compares and
+    /// serializes stably. Defaults to no rounding.
+    pub duration_precision: Option<u8>,
+    /// Look for the ID3v1 and APEv2 tags at the end of MP3 files. Turning this
+    /// off leaves only what's at the front of the file to go by, as when the
+    /// data is the start of a stream rather than a whole file.
+    pub read_trailing_tags: bool,
+    /// MP3 files larger than this many bytes have only a sample of their frames
+    /// read, rather than every one, with the duration coming from the Xing/Info
+    /// or VBRI header or else estimated from the frames sampled. `None` always
+    /// reads every frame. Defaults to `DEFAULT_MP3_SCAN_LIMIT`.
+    pub mp3_scan_limit: Option<usize>,
+    /// The encoding of ID3v1 tags, which don't say, e.g.
+    /// `encoding_rs::SHIFT_JIS` for Japanese files. By default they're read as
+    /// UTF-8 where that's valid, and as Latin-1 where it isn't.
+    pub id3v1_encoding: Option<&'static encoding_rs::Encoding>,
+    /// Fail reads that can't determine the format, duration and sample rate,
+    /// rather than returning what could be read. Applies to
+    /// `read_any_with_options` and `try_read_any_with_options`.
+    pub strict: bool,
+    /// Put the text of tags into Unicode Normalization Form C, so that text
+    /// with decomposed accents compares equal to the same text with composed
+    /// ones. Applies to `read_any_with_options`.
+    pub normalize_unicode: bool,
+}
+
+/// The size past which MP3 files are sampled rather than read in full, unless
+/// `ReadOptions` says otherwise: about half an hour at 320 kbps.
+pub const DEFAULT_MP3_SCAN_LIMIT: usize = 64 * 1024 * 1024;
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            artist_from_album_artist_fallback: true,
+            prefer_album_artist: false,
+            duration_precision: None,
+            read_trailing_tags: true,
+            mp3_scan_limit: Some(DEFAULT_MP3_SCAN_LIMIT),
+            id3v1_encoding: None,
+            strict: false,
+            normalize_unicode: false,
+        }
+    }
+}
+
+/// The offset of the Xing/Info header within a frame, which follows the frame
+/// header, the CRC and the side information.
+fn mp3_xing_offset(frame: &mp3_metadata::Frame) -> usize {
+    use mp3_metadata::{ChannelType, Version, CRC};
+
+    let mono = frame.chan_type == ChannelType::SingleChannel;
+    let crc = if frame.crc == CRC::Added { 2 } else { 0 };
+
+    4 + crc
+        + match (frame.version == Version::MPEG1, mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        }
+}
+
+/// The number of frames in the stream, as declared by the Xing/Info or VBRI
+/// header in its first frame.
+fn mp3_header_frame_count(data: &[u8], first: &mp3_metadata::Frame) -> Option<u32> {
+    use std::convert::TryInto;
+
+    let frame = data.get(first.offset as usize..)?;
+    let u32_be = |offset: usize| {
+        Some(u32::from_be_bytes(
+            frame.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    let xing = mp3_xing_offset(first);
+    match frame.get(xing..xing + 4) {
+        // the frame count is the first of the optional fields, when flagged
+        Some(b"Xing" | b"Info") if u32_be(xing + 4)? & 1 != 0 => u32_be(xing + 8),
+        Some(b"Xing" | b"Info") => None,
+        // after VBRI's version, delay, quality and byte count
+        _ if frame.get(36..40) == Some(b"VBRI") => u32_be(36 + 14),
+        _ => None,
+    }
+}
+
+/// Tells the bitrate mode from the Xing/Info or VBRI header of the first frame,
+/// falling back to comparing the bitrates of all frames.
+fn mp3_bitrate_mode(data: &[u8], frames: &[mp3_metadata::Frame]) -> BitrateMode {
+    let first = match frames.first() {
+        Some(first) => first,
+        None => return BitrateMode::Unknown,
+    };
+    let frame = data.get(first.offset as usize..).unwrap_or_default();
+    let xing = mp3_xing_offset(first);
+
+    match frame.get(xing..xing + 4) {
+        Some(tag @ (b"Xing" | b"Info")) => {
+            // the flags say which of the optional fields precede the LAME extension
+            let flags = frame.get(xing + 7).copied().unwrap_or_default();
+            let lame = xing
+                + 8
+                + 4 * usize::from(flags & 1)
+                + 4 * usize::from(flags >> 1 & 1)
+                + 100 * usize::from(flags >> 2 & 1)
+                + 4 * usize::from(flags >> 3 & 1);
+
+            // the low nibble of the byte after the 9 byte encoder version
+            let method = match frame.get(lame..lame + 4) {
+                Some(b"LAME" | b"Lavc" | b"Lavf") => frame.get(lame + 9).map(|byte| byte & 0x0F),
                 _ => None,
-            })
+            };
+
+            match method {
+                Some(1 | 8) => BitrateMode::Cbr,
+                Some(2 | 9) => BitrateMode::Abr,
+                Some(3..=6) => BitrateMode::Vbr,
+                // LAME writes `Info` for CBR streams, and `Xing` otherwise
+                _ if tag == b"Info" => BitrateMode::Cbr,
+                _ => BitrateMode::Vbr,
+            }
+        }
+        // Fraunhofer's VBRI header is always at the same offset, and only used for VBR
+        _ if frame.get(36..40) == Some(b"VBRI") => BitrateMode::Vbr,
+        _ if frames.iter().all(|frame| frame.bitrate == first.bitrate) => BitrateMode::Cbr,
+        _ => BitrateMode::Vbr,
+    }
+}
+
+/// Fills in the stream parameters from the MPEG audio frames.
+fn set_mpeg_stream(metadata: &mut Metadata, data: &[u8], res: &mp3_metadata::MP3Metadata) {
+    let header_frame_count = res
+        .frames
+        .first()
+        .and_then(|first| mp3_header_frame_count(data, first));
+
+    // the frame of a Xing/Info or VBRI header holds no audio, and its bitrate
+    // is up to the encoder
+    let audio_frames = match header_frame_count {
+        Some(_) => &res.frames[1..],
+        None => &res.frames[..],
+    };
+
+    if let Some(frame) = res.frames.first() {
+        use mp3_metadata::{ChannelType, Emphasis, CRC};
+
+        metadata.channels = match frame.chan_type {
+            ChannelType::SingleChannel => Some(1),
+            ChannelType::Unknown => None,
+            _ => Some(2),
+        };
+
+        metadata.stereo_mode = match frame.chan_type {
+            ChannelType::Stereo => Some("stereo"),
+            ChannelType::JointStereo => Some("joint_stereo"),
+            ChannelType::DualChannel => Some("dual_channel"),
+            ChannelType::SingleChannel => Some("mono"),
+            ChannelType::Unknown => None,
+        }
+        .map(String::from);
+
+        metadata.crc_protected = Some(frame.crc == CRC::Added);
+        metadata.emphasis = match frame.emphasis {
+            Emphasis::None => Some("none"),
+            Emphasis::MicroSeconds => Some("50_15_us"),
+            Emphasis::Reserved => Some("reserved"),
+            Emphasis::CCITT => Some("ccitt_j17"),
+            Emphasis::Unknown => None,
+        }
+        .map(String::from);
+
+        metadata.sample_rate = Some(frame.sampling_freq.into());
+
+        // every frame holds the same number of samples, so the plain mean of
+        // their bitrates is the average over time, rather than the first
+        // frame's, which VBR encoders typically give to their Xing header
+        let bitrates = audio_frames.iter().map(|frame| f64::from(frame.bitrate));
+        let average_bitrate = Some(bitrates.clone().sum::<f64>() / audio_frames.len() as f64)
+            .filter(|_| !audio_frames.is_empty());
+        metadata.bitrate = average_bitrate;
+        metadata.min_bitrate = bitrates.clone().reduce(f64::min);
+        metadata.max_bitrate = bitrates.reduce(f64::max);
+
+        metadata.effective_bitrate_per_channel = metadata
+            .channels
+            .zip(average_bitrate)
+            .map(|(channels, bitrate)| bitrate / f64::from(channels));
+    }
+
+    metadata.bitrate_mode = Some(mp3_bitrate_mode(data, &res.frames));
+
+    // without the header's frame, like `read_mp3_fast` and the sampled frames
+    metadata.seconds = match (res.frames.first(), header_frame_count) {
+        (Some(first), Some(count)) => first
+            .duration
+            .map(|duration| duration.as_secs_f64() * f64::from(count)),
+        _ => Some(res.duration.as_secs_f64()),
+    };
+    metadata.frame_count = header_frame_count
+        .map(u64::from)
+        .or(Some(res.frames.len() as u64));
+}
+
+/// The frames of a few windows spread evenly over `audio`, which runs to the
+/// end of the file, for files too large to read every frame of. The first
+/// window holds the Xing/Info header and the stream parameters, so failing to
+/// read it fails the whole.
+fn mp3_sampled_frames(audio: &[u8]) -> Result<mp3_metadata::MP3Metadata, mp3_metadata::Error> {
+    const WINDOWS: u64 = 16;
+    const WINDOW_LEN: usize = 64 * 1024;
+
+    let last = audio.len().saturating_sub(WINDOW_LEN);
+    let window = |index: u64| {
+        let start = (last as u64 * index / (WINDOWS - 1)) as usize;
+        &audio[start..(start + WINDOW_LEN).min(audio.len())]
+    };
+
+    let mut res = mp3_metadata::read_from_slice(window(0))?;
+
+    for index in 1..WINDOWS {
+        if let Ok(sampled) = mp3_metadata::read_from_slice(window(index)) {
+            res.frames.extend(sampled.frames);
+        }
+    }
+
+    Ok(res)
+}
+
+/// The duration of an MP3 file from a sample of its frames: from the
+/// Xing/Info or VBRI header's frame count, or else from the bytes the frames
+/// take up per second, with a warning.
+fn mp3_sampled_seconds(
+    metadata: &mut Metadata,
+    audio: &[u8],
+    frames: &[mp3_metadata::Frame],
+) -> Option<f64> {
+    let first = frames.first()?;
+
+    if let Some(count) = mp3_header_frame_count(audio, first) {
+        return Some(first.duration?.as_secs_f64() * f64::from(count));
+    }
+
+    let end = audio.len() - id3v1_len(audio);
+    let audio_len = end - ape::len(&audio[..end]);
+
+    let (bytes, seconds) = frames
+        .iter()
+        .fold((0_f64, 0_f64), |(bytes, seconds), frame| {
+            let duration = frame
+                .duration
+                .map_or(0_f64, |duration| duration.as_secs_f64());
+            (bytes + f64::from(frame.size), seconds + duration)
+        });
+
+    metadata.warn(String::from(
+        "estimated the duration from a sample of the frames, as the file is past the scan limit",
+    ));
+
+    Some(audio_len as f64 * seconds / bytes).filter(|seconds| seconds.is_finite())
+}
+
+/// The stream parameters of an MP3 file, skipping the ID3 tags.
+fn read_mp3_stream(reader: &[u8]) -> Option<Metadata> {
+    if reader.len() < 24 {
+        return None;
+    }
+
+    let audio = &reader[mp3_audio_offset(reader)..];
+    let res = mp3_metadata::read_from_slice(audio).ok()?;
+    res.frames.first()?;
+
+    let mut metadata = Metadata::empty(Format::Mp3);
+    set_mpeg_stream(&mut metadata, audio, &res);
+
+    Some(metadata)
+}
+
+/// Fills in the fields that come from an ID3v2 tag.
+fn set_id3_tags(metadata: &mut Metadata, tag: &id3::Tag, options: &ReadOptions) {
+    use id3::TagLike;
+
+    let artist = if options.prefer_album_artist {
+        tag.album_artist().or_else(|| tag.artist())
+    } else if options.artist_from_album_artist_fallback {
+        tag.artist().or_else(|| tag.album_artist())
+    } else {
+        tag.artist()
+    };
+
+    if let Some(artist) = artist {
+        metadata.artist = Some(String::from(artist))
+    }
+
+    if let Some(album) = tag.album() {
+        metadata.album = Some(String::from(album))
+    }
+
+    if let Some(title) = tag.title() {
+        metadata.title = Some(String::from(title))
+    }
+
+    metadata.comment = id3_comment(tag).map(String::from);
+    metadata.genre = id3_genre(tag);
+    metadata.date = id3_date(tag).map(String::from);
+    metadata.disc_subtitle = id3_disc_subtitle(tag).map(String::from);
+    metadata.original_artist = id3_original_artist(tag);
+    (metadata.track_number, metadata.total_tracks) =
+        id3_text(tag, "TRCK").map_or((None, None), parse_position);
+    (metadata.disc_number, metadata.total_discs) =
+        id3_text(tag, "TPOS").map_or((None, None), parse_position);
+    metadata.original_album = id3_text(tag, "TOAL").map(String::from);
+    metadata.original_year = id3_original_year(tag);
+
+    // iTunes' podcast frames, which aren't part of the ID3 spec. `PCST` holds
+    // a 32-bit flag that is nonzero for podcasts
+    metadata.podcast = tag.get("PCST").map(|frame| {
+        frame
+            .content()
+            .to_unknown()
+            .map_or(true, |flag| flag.data.iter().any(|&byte| byte != 0))
+    });
+    metadata.podcast_url = tag
+        .get("WFED")
+        .and_then(|frame| frame.content().link())
+        // iTunes writes `WFED` like a text frame, with an encoding byte and
+        // a null terminator around the URL
+        .map(|url| String::from(url.trim_matches(|c: char| c.is_control())));
+    metadata.episode_guid = tag
+        .get("TGID")
+        .and_then(|frame| frame.content().text())
+        .map(String::from);
+    metadata.podcast_category = tag
+        .get("TCAT")
+        .and_then(|frame| frame.content().text())
+        .map(String::from);
+
+    metadata.gapless = tag
+        .extended_texts()
+        .find(|text| text.description == "iTunPGAP")
+        .and_then(|text| match text.value.trim() {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        });
+
+    metadata.media_type = tag
+        .extended_texts()
+        .find(|text| text.description == "ITUNESMEDIATYPE")
+        .and_then(|text| parse_media_type(&text.value))
+        .or(metadata
+            .podcast
+            .filter(|&podcast| podcast)
+            .map(|_| "podcast"))
+        .map(String::from);
+
+    metadata.seconds = tag
+        .duration()
+        .map(|miliseconds| f64::from(miliseconds) / 1_000_f64);
+
+    // iTunes writes Sound Check as a comment, other taggers as a `TXXX` frame
+    metadata.sound_check = tag
+        .extended_texts()
+        .find(|text| text.description == "iTunNORM")
+        .map(|text| text.value.as_str())
+        .or_else(|| {
+            tag.comments()
+                .find(|comment| comment.description == "iTunNORM")
+                .map(|comment| comment.text.as_str())
         })
-        .and_then(
-            |(
-                track,
-                &AudioSampleEntry {
-                    codec_type,
-                    channelcount,
-                    samplesize,
-                    samplerate,
-                    ..
-                },
-            )| {
-                let format = match codec_type {
-                    CodecType::MP3 => Format::Mp3,
-                    CodecType::AAC => Format::Aac,
-                    CodecType::ALAC => Format::Alac,
-                    CodecType::AV1 => Format::Av1,
-                    CodecType::Opus => Format::Opus,
-                    CodecType::FLAC => Format::Flac,
-                    CodecType::VP8 => Format::Vp8,
-                    CodecType::VP9 => Format::Vp9,
-                    _ => return None,
-                };
+        .and_then(parse_sound_check);
 
-                Some(Metadata {
-                    channels: Some(channelcount),
-                    sample_rate: Some(samplerate),
-                    bit_depth: Some(samplesize),
-                    seconds: track.duration.and_then(|duration| {
-                        track
-                            .timescale
-                            .map(|timescale| (duration.0 as f64 / timescale.0 as f64))
-                    }),
-                    ..Metadata::empty(format)
-                })
-            },
-        )
+    // prefer the `RVA2` frame for the whole track over an album's
+    let rva2 = tag
+        .frames()
+        .filter(|frame| frame.id() == "RVA2")
+        .filter_map(|frame| frame.content().to_unknown().ok())
+        .map(|frame| frame.data.clone())
+        .collect::<Vec<_>>();
+    metadata.volume_adjustment_db = rva2
+        .iter()
+        .filter(|data| !data.starts_with(b"album\0"))
+        .chain(&rva2)
+        .find_map(|data| parse_rva2(data))
+        .or_else(|| {
+            tag.get("RVAD")
+                .and_then(|frame| frame.content().to_unknown().ok())
+                .and_then(|frame| parse_rvad(&frame.data))
+        });
+
+    metadata.set_extra(id3_extra(tag));
+
+    let cover = id3_cover(tag);
+    metadata.check_cover_size(cover.map(|cover| cover.data.len()));
+    metadata.set_cover_dimensions(cover.and_then(|cover| picture::dimensions(&cover.data)));
 }
 
-pub fn read_wav(reader: &[u8]) -> Option<Metadata> {
-    use hound::{WavReader, WavSpec};
+pub fn read_mp3(reader: &[u8]) -> Option<Metadata> {
+    read_mp3_with_options(reader, &ReadOptions::default())
+}
 
-    let reader = WavReader::new(reader).ok()?;
+pub fn read_mp3_with_options(reader: &[u8], options: &ReadOptions) -> Option<Metadata> {
+    read_mp3_sourced(reader, options, None)
+}
 
-    let WavSpec {
-        channels,
-        sample_rate,
-        bits_per_sample,
-        ..
-    } = reader.spec();
+/// Like `read_mp3`, but also says where each field came from, for making sense
+/// of files whose tags disagree: a map from the name of each field found, e.g.
+/// `artist`, to its source. That's `ID3v2` followed by the frame, e.g.
+/// `ID3v2 TPE1` or `ID3v2 TXXX:iTunNORM`, or `ID3v1`, `APEv2`, or `MPEG audio` for a duration measured
+/// from the frames rather than taken from `TLEN`.
+pub fn read_mp3_attributed(data: &[u8]) -> Option<(Metadata, HashMap<String, String>)> {
+    let mut sources = Sources::default();
+    let metadata = read_mp3_sourced(data, &ReadOptions::default(), Some(&mut sources))?;
 
-    Some(Metadata {
-        seconds: Some(f64::from(reader.duration()) / f64::from(sample_rate)),
-        sample_rate: Some(sample_rate.into()),
-        bit_depth: Some(bits_per_sample),
-        channels: Some(channels.into()),
-        bitrate: Some(
-            f64::from(sample_rate) * f64::from(channels) * f64::from(bits_per_sample) / 1_024_f64,
-        ),
-        ..Metadata::empty(Format::Wav)
-    })
+    Some((metadata, sources.fields))
 }
 
-#[wasm_bindgen]
-pub fn fazer(data: Vec<u8>) -> Result<IMetadata, JsError> {
-    let metadata = read_mp4(&data)
-        .or_else(|| read_ogg(&data))
-        .or_else(|| read_flac(&data))
-        .or_else(|| read_wav(&data))
-        .or_else(|| read_mp3(&data));
-
-    Ok(serde_wasm_bindgen::to_value(&metadata)?.unchecked_into())
+/// Renders the value of each field `read_mp3_attributed` attributes, so that
+/// it can tell which ones a source changed.
+macro_rules! attributed_fields {
+    ($($field:ident),* $(,)?) => {
+        [$((stringify!($field), |metadata: &Metadata| {
+            metadata.$field.as_ref().map(|value| format!("{:?}", value))
+        })),*]
+    };
+}
+
+type RenderFn = fn(&Metadata) -> Option<String>;
+
+const ATTRIBUTED_FIELDS: [(&str, RenderFn); 24] = attributed_fields!(
+    artist,
+    album,
+    title,
+    comment,
+    genre,
+    date,
+    disc_subtitle,
+    original_artist,
+    original_album,
+    original_year,
+    track_number,
+    total_tracks,
+    disc_number,
+    total_discs,
+    podcast,
+    podcast_url,
+    episode_guid,
+    podcast_category,
+    gapless,
+    media_type,
+    seconds,
+    sound_check,
+    volume_adjustment_db,
+    extra,
+);
+
+/// The ID3v2 frames each field can come from, in the order `set_id3_tags`
+/// tries them. The `TXXX` and `COMM` frames are told apart by the description
+/// after the colon, which is empty for the comment itself.
+const ID3_FIELD_FRAMES: [(&str, &[&str]); 23] = [
+    ("artist", &["TPE1", "TPE2"]),
+    ("album", &["TALB"]),
+    ("title", &["TIT2"]),
+    ("comment", &["COMM:"]),
+    ("genre", &["TCON"]),
+    ("date", &["TDRC", "TYER"]),
+    ("disc_subtitle", &["TSST"]),
+    ("original_artist", &["TOPE"]),
+    ("original_album", &["TOAL"]),
+    ("original_year", &["TORY", "TDOR"]),
+    ("track_number", &["TRCK"]),
+    ("total_tracks", &["TRCK"]),
+    ("disc_number", &["TPOS"]),
+    ("total_discs", &["TPOS"]),
+    ("podcast", &["PCST"]),
+    ("podcast_url", &["WFED"]),
+    ("episode_guid", &["TGID"]),
+    ("podcast_category", &["TCAT"]),
+    ("gapless", &["TXXX:iTunPGAP"]),
+    ("media_type", &["TXXX:ITUNESMEDIATYPE", "PCST"]),
+    ("seconds", &["TLEN"]),
+    ("sound_check", &["TXXX:iTunNORM", "COMM:iTunNORM"]),
+    ("volume_adjustment_db", &["RVA2", "RVAD"]),
+];
+
+/// The source of each field `read_mp3_attributed` has seen filled in so far.
+#[derive(Default)]
+struct Sources {
+    fields: HashMap<String, String>,
+    /// The values of `ATTRIBUTED_FIELDS` as of the last source.
+    values: Vec<Option<String>>,
+}
+
+impl Sources {
+    /// Attributes the fields that changed since the last source to `source`,
+    /// which is given the name of each.
+    fn update(&mut self, metadata: &Metadata, source: &dyn Fn(&str) -> String) {
+        self.values.resize(ATTRIBUTED_FIELDS.len(), None);
+
+        for ((name, render), value) in ATTRIBUTED_FIELDS.iter().zip(&mut self.values) {
+            let rendered = render(metadata);
+            if rendered != *value {
+                match rendered {
+                    Some(_) => self.fields.insert(String::from(*name), source(name)),
+                    None => self.fields.remove(*name),
+                };
+                *value = rendered;
+            }
+        }
+    }
+}
+
+/// The frame of `tag` that `field` came from, as `read_mp3_attributed` names
+/// it, e.g. `ID3v2 TPE1` or `ID3v2 TXXX:iTunNORM`.
+fn id3_source(tag: &id3::Tag, field: &str) -> String {
+    let present = |frame: &str| {
+        let (id, description) = match frame.split_once(':') {
+            Some((id, description)) => (id, Some(description)),
+            None => (frame, None),
+        };
+
+        tag.frames().filter(|frame| frame.id() == id).any(|frame| {
+            let content = frame.content();
+            let found = content
+                .extended_text()
+                .map(|text| text.description.as_str())
+                .or_else(|| {
+                    content
+                        .comment()
+                        .map(|comment| comment.description.as_str())
+                });
+            description.is_none_or(|description| found == Some(description))
+        })
+    };
+
+    let frame = ID3_FIELD_FRAMES
+        .iter()
+        .find(|&&(name, _)| name == field)
+        .and_then(|(_, frames)| frames.iter().find(|&&frame| present(frame)));
+
+    match frame {
+        Some(frame) => format!("ID3v2 {}", frame.trim_end_matches(':')),
+        None => String::from("ID3v2"),
+    }
+}
+
+fn read_mp3_sourced(
+    reader: &[u8],
+    options: &ReadOptions,
+    mut sources: Option<&mut Sources>,
+) -> Option<Metadata> {
+    let mut attribute = |metadata: &Metadata, source: &dyn Fn(&str) -> String| {
+        if let Some(sources) = sources.as_deref_mut() {
+            sources.update(metadata, source);
+        }
+    };
+
+    // the smallest possible MPEG audio frame, layer III at 8 kbps and 24 kHz
+    if reader.len() < 24 {
+        return None;
+    }
+
+    let trailing_tags = options.read_trailing_tags;
+    let mut metadata = Metadata {
+        has_id3v1: trailing_tags.then(|| id3v1_len(reader) > 0),
+        has_id3v2: Some(id3v2_declared_len(reader).is_some()),
+        ..Metadata::empty(Format::Mp3)
+    };
+
+    let tag = id3::Tag::read_from(reader);
+
+    // a tag that fails to parse still leaves the audio frames to read from
+    if let (Err(err), true) = (&tag, reader.starts_with(b"ID3")) {
+        metadata.warn(format!("failed to read the ID3v2 tag: {}", err));
+    }
+
+    if let Ok(res) = tag {
+        set_id3_tags(&mut metadata, &res, options);
+        attribute(&metadata, &|field| id3_source(&res, field));
+    }
+
+    // `mp3_metadata` is only handed the audio, so that it can't find frame
+    // headers inside a tag it sizes differently
+    let audio = &reader[mp3_audio_offset(reader)..];
+    let sampled = options
+        .mp3_scan_limit
+        .is_some_and(|limit| reader.len() > limit);
+    let frames = if sampled {
+        mp3_sampled_frames(audio)
+    } else {
+        mp3_metadata::read_from_slice(audio)
+    };
+
+    if let Err(err) = &frames {
+        metadata.warn(format!("failed to read the MPEG audio frames: {}", err));
+    }
+
+    if let Ok(res) = frames {
+        set_mpeg_stream(&mut metadata, audio, &res);
+
+        // only the frames sampled were counted
+        if sampled {
+            metadata.seconds = mp3_sampled_seconds(&mut metadata, audio, &res.frames);
+            metadata.frame_count = res
+                .frames
+                .first()
+                .and_then(|first| mp3_header_frame_count(audio, first))
+                .map(u64::from);
+        }
+        attribute(&metadata, &|_| String::from("MPEG audio"));
+
+        // the ID3v2 tag as `mp3_metadata` reads it, which it may manage when
+        // `id3` can't
+        for tag in res.optional_info {
+            if metadata.title.is_none() {
+                if let Some(title) = tag.title {
+                    metadata.title = Some(title)
+                }
+            }
+
+            if metadata.original_artist.is_none() {
+                metadata.original_artist =
+                    join_artists(tag.original_artists.iter().map(String::as_str))
+            }
+        }
+        attribute(&metadata, &|_| String::from("ID3v2"));
+    }
+
+    // the ID3v1 tag only fills in what the ID3v2 tag didn't have
+    if let Some(tag) = read_id3v1(reader, options.id3v1_encoding).filter(|_| trailing_tags) {
+        metadata.merge(&tag);
+        attribute(&metadata, &|_| String::from("ID3v1"));
+    }
+
+    // a misplaced ID3v1 tag is read like one at the end, even with
+    // `read_trailing_tags` off, as it's in the way of the audio either way
+    if let Some(tag) = leading_id3v1(reader) {
+        metadata.warn(String::from("found an ID3v1 tag at the start of the file"));
+
+        if let Some(tag) = read_id3v1(tag, options.id3v1_encoding) {
+            metadata.merge(&tag);
+            attribute(&metadata, &|_| String::from("ID3v1"));
+        }
+    }
+
+    // where the audio ends, before any trailing tags
+    let mut end = reader.len();
+
+    if trailing_tags {
+        end -= id3v1_len(reader);
+
+        // and an APEv2 tag, which sits in front of any ID3v1 tag, after that
+        if let Some(items) = ape::items(&reader[..end]) {
+            let get = |key: &str| items.get(key).cloned();
+
+            metadata.merge(&Metadata {
+                artist: get("ARTIST"),
+                album: get("ALBUM"),
+                title: get("TITLE"),
+                date: get("YEAR"),
+                genre: get("GENRE"),
+                ..Metadata::empty(Format::Mp3)
+            });
+            attribute(&metadata, &|_| String::from("APEv2"));
+        }
+
+        end -= ape::len(&reader[..end]);
+    }
+
+    let audio = &reader[mp3_audio_offset(reader).min(end)..end];
+    metadata.audio_md5 = audio_md5([audio]);
+    metadata.audio_range = Some(region_range(reader, audio));
+
+    metadata.round_seconds(options.duration_precision);
+
+    Some(metadata)
+}
+
+/// Like `read_mp3`, but much faster on long files, as only the ID3v2 tag and the
+/// first 64 KiB of audio are read, rather than every frame. So the duration
+/// comes from the Xing/Info or VBRI header, or, if the frames read all have the
+/// same bitrate, is estimated from the file size (with a warning, as a VBR file
+/// could start out that way). Failing both it comes from the `TLEN` frame, and
+/// is `None` for files that don't have one either. The bitrate mode is judged
+/// from the frames read.
+pub fn read_mp3_fast(reader: &[u8]) -> Option<Metadata> {
+    const AUDIO_PREFIX_LEN: usize = 64 * 1024;
+
+    if reader.len() < 24 {
+        return None;
+    }
+
+    // the ID3v1 tag is at the very end, so it's a cheap look even here
+    let mut metadata = Metadata {
+        has_id3v1: Some(id3v1_len(reader) > 0),
+        has_id3v2: Some(id3v2_declared_len(reader).is_some()),
+        ..Metadata::empty(Format::Mp3)
+    };
+
+    match id3::Tag::read_from(reader) {
+        Ok(tag) => set_id3_tags(&mut metadata, &tag, &ReadOptions::default()),
+        Err(err) if reader.starts_with(b"ID3") => {
+            metadata.warn(format!("failed to read the ID3v2 tag: {}", err));
+        }
+        Err(_) => {}
+    }
+
+    let audio = &reader[mp3_audio_offset(reader)..];
+    let audio = &audio[..audio.len().min(AUDIO_PREFIX_LEN)];
+
+    let res = match mp3_metadata::read_from_slice(audio) {
+        Ok(res) => res,
+        Err(err) => {
+            metadata.warn(format!("failed to read the MPEG audio frames: {}", err));
+            return Some(metadata);
+        }
+    };
+
+    let tag_seconds = metadata.seconds;
+    set_mpeg_stream(&mut metadata, audio, &res);
+
+    // only the frames read were counted, which isn't all of them
+    let frame_count = res
+        .frames
+        .first()
+        .and_then(|first| mp3_header_frame_count(audio, first));
+    metadata.frame_count = frame_count.map(u64::from);
+
+    metadata.seconds = res
+        .frames
+        .first()
+        .zip(frame_count)
+        .and_then(|(first, count)| Some(first.duration?.as_secs_f64() * f64::from(count)));
+
+    // without a header to count the frames, a constant bitrate still gives the
+    // duration away, as long as the frames read are all there is to go by
+    if metadata.seconds.is_none() && matches!(metadata.bitrate_mode, Some(BitrateMode::Cbr)) {
+        let end = reader.len() - id3v1_len(reader);
+        let end = end - ape::len(&reader[..end]);
+        let audio_len = end.saturating_sub(mp3_audio_offset(reader));
+
+        if let Some(bitrate) = metadata.bitrate.filter(|&bitrate| bitrate > 0_f64) {
+            metadata.seconds = Some(audio_len as f64 * 8_f64 / (bitrate * 1000_f64));
+            metadata.warn(String::from(
+                "estimated the duration from the file size, assuming a constant bitrate",
+            ));
+        }
+    }
+
+    metadata.seconds = metadata.seconds.or(tag_seconds);
+
+    Some(metadata)
+}
+
+/// Builds a `(timestamp_seconds, byte_offset)` seek table from the MP3 frame
+/// headers. Every frame gets an entry for VBR streams, while CBR streams (where
+/// offsets are linear in time) only get one entry per second.
+pub fn build_mp3_seek_table(reader: &[u8]) -> Option<Vec<(f64, u64)>> {
+    let audio_offset = mp3_audio_offset(reader);
+    let res = mp3_metadata::read_from_slice(&reader[audio_offset..]).ok()?;
+
+    let first_bitrate = res.frames.first()?.bitrate;
+    let is_cbr = res
+        .frames
+        .iter()
+        .all(|frame| frame.bitrate == first_bitrate);
+
+    let mut table: Vec<(f64, u64)> = Vec::new();
+
+    for frame in &res.frames {
+        let timestamp = frame.position.as_secs_f64();
+
+        if is_cbr {
+            if let Some(&(last, _)) = table.last() {
+                if timestamp - last < 1_f64 {
+                    continue;
+                }
+            }
+        }
+
+        table.push((timestamp, u64::from(frame.offset) + audio_offset as u64));
+    }
+
+    Some(table)
+}
+
+/// Reads the `(sample_number, byte_offset)` seek points from the FLAC SEEKTABLE
+/// block. Offsets are from the start of the file, rather than from the first
+/// frame as in the block itself, and placeholder points are skipped.
+pub fn read_flac_seektable(data: &[u8]) -> Option<Vec<(u64, u64)>> {
+    use metaflac::{Block, Tag};
+    use std::convert::TryInto;
+
+    const PLACEHOLDER: u64 = u64::MAX;
+
+    let mut frames = data;
+    let tag = Tag::read_from(&mut frames).ok()?;
+    let first_frame = (data.len() - frames.len()) as u64;
+
+    let seektable = tag.blocks().find_map(|block| match block {
+        Block::SeekTable(seektable) => Some(seektable),
+        _ => None,
+    })?;
+
+    // `metaflac` keeps the seek point fields private, so go through their
+    // serialized form: a 64-bit sample number, then a 64-bit offset
+    let points = seektable
+        .seekpoints
+        .iter()
+        .filter_map(|point| {
+            let bytes = point.to_bytes();
+            let sample_number = u64::from_be_bytes(bytes.get(..8)?.try_into().ok()?);
+            let offset = u64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?);
+
+            Some((sample_number, offset))
+        })
+        .filter(|&(sample_number, _)| sample_number != PLACEHOLDER)
+        .map(|(sample_number, offset)| (sample_number, first_frame.saturating_add(offset)))
+        .collect();
+
+    Some(points)
+}
+
+/// Reads the `(id, payload)` of every APPLICATION block, where tools like
+/// CUETools keep their own data. IDs are the registered 4 character codes.
+pub fn read_flac_application(data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    use metaflac::{Block, Tag};
+
+    let tag = Tag::read_from(&mut { data }).ok()?;
+
+    let blocks = tag
+        .blocks()
+        .filter_map(|block| match block {
+            Block::Application(application) => Some((
+                String::from_utf8_lossy(&application.id).into_owned(),
+                application.data.clone(),
+            )),
+            _ => None,
+        })
+        .collect();
+
+    Some(blocks)
+}
+
+/// The total size of a FLAC file's PADDING blocks, block headers excluded, which
+/// is how much a tag can grow by without rewriting the audio. `Some(0)` if
+/// there aren't any, and `None` for files that aren't FLAC.
+pub fn flac_padding_bytes(data: &[u8]) -> Option<u32> {
+    use metaflac::{Block, Tag};
+
+    let tag = Tag::read_from(&mut { data }).ok()?;
+
+    let padding = tag
+        .blocks()
+        .map(|block| match block {
+            Block::Padding(len) => *len,
+            _ => 0,
+        })
+        .fold(0, u32::saturating_add);
+
+    Some(padding)
+}
+
+/// The stream parameters of a FLAC file, straight from its STREAMINFO block,
+/// without reading any of the other metadata blocks.
+fn read_flac_stream(reader: &[u8]) -> Option<Metadata> {
+    use std::convert::TryInto;
+
+    // STREAMINFO has to be the first block, right after the magic
+    let stream_info = match reader {
+        [b'f', b'L', b'a', b'C', block_type, ..] if block_type & 0x7F == 0 => reader.get(8..42)?,
+        _ => return None,
+    };
+
+    // 20 bits of sample rate, 3 of channels, 5 of bit depth and 36 of samples
+    let packed = u64::from_be_bytes(stream_info[10..18].try_into().ok()?);
+    let sample_rate = (packed >> 44) as u32;
+    let total_samples = packed & 0xF_FFFF_FFFF;
+
+    let mut metadata = Metadata {
+        channels: Some((packed >> 41 & 0x7) as u32 + 1),
+        sample_rate: Some(sample_rate.into()),
+        bit_depth: Some((packed >> 36 & 0x1F) as u16 + 1),
+        ..Metadata::empty(Format::Flac)
+    };
+
+    metadata.seconds = if total_samples > 0 {
+        Some(total_samples as f64 / f64::from(sample_rate)).filter(|_| sample_rate > 0)
+    } else {
+        flac_estimated_seconds(&mut metadata, reader, sample_rate)
+    };
+
+    Some(metadata)
+}
+
+/// Estimates the duration of a FLAC stream whose STREAMINFO leaves the sample
+/// count at zero from how far its frames go, warning that it's an estimate.
+fn flac_estimated_seconds(metadata: &mut Metadata, frames: &[u8], sample_rate: u32) -> Option<f64> {
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let samples = flac::samples_written(frames)?;
+    metadata.warn("the FLAC stream doesn't declare its length, so `seconds` is estimated");
+
+    Some(samples as f64 / f64::from(sample_rate))
+}
+
+/// The speakers FLAC assigns to each channel count, as a WAV channel mask.
+fn flac_channel_mask(channels: u8) -> Option<u32> {
+    match channels {
+        1 => Some(0x4),
+        2 => Some(0x3),
+        3 => Some(0x7),
+        4 => Some(0x33),
+        5 => Some(0x37),
+        6 => Some(0x3F),
+        7 => Some(0x70F),
+        8 => Some(0x63F),
+        _ => None,
+    }
+}
+
+pub fn read_flac(reader: &[u8]) -> Option<Metadata> {
+    use metaflac::{Block, Tag};
+
+    // `fLaC`, followed by the mandatory STREAMINFO block
+    if reader.len() < 42 {
+        return None;
+    }
+
+    // reading the tag advances `frames` past the metadata blocks
+    let mut frames = reader;
+    let tag = match Tag::read_from(&mut frames) {
+        Ok(tag) => tag,
+        // the magic matched, so this is still a FLAC file, just with broken metadata
+        Err(err) if reader.starts_with(b"fLaC") => {
+            let mut metadata = Metadata::empty(Format::Flac);
+            metadata.warn(format!("failed to read the FLAC metadata: {}", err));
+            return Some(metadata);
+        }
+        Err(_) => return None,
+    };
+
+    let mut metadata = Metadata {
+        bitrate_mode: Some(BitrateMode::Lossless),
+        audio_range: Some(region_range(reader, frames)),
+        ..Metadata::empty(Format::Flac)
+    };
+
+    for block in tag.blocks() {
+        if let Block::StreamInfo(stream_info) = block {
+            metadata.seconds = if stream_info.total_samples > 0 {
+                Some(stream_info.total_samples as f64 / f64::from(stream_info.sample_rate))
+            } else {
+                // unknown, as while the file is still being recorded
+                flac_estimated_seconds(&mut metadata, frames, stream_info.sample_rate)
+            };
+            // left/side, right/side and mid/side stereo are decorrelated per
+            // frame, and STREAMINFO still counts them as two channels
+            metadata.channels = Some(stream_info.num_channels.into());
+            metadata.sample_rate = Some(stream_info.sample_rate.into());
+            metadata.bit_depth = Some(stream_info.bits_per_sample.into());
+            // all zeros if the encoder didn't compute it
+            metadata.audio_md5 = Some(hex(&stream_info.md5))
+                .filter(|_| stream_info.md5.iter().any(|&byte| byte != 0));
+            metadata.channel_layout =
+                flac_channel_mask(stream_info.num_channels).and_then(riff::channel_layout);
+
+            // frame sizes vary, so this can only tell if there aren't enough bytes
+            // left for every frame to be the smallest one the encoder wrote
+            let block_size = u64::from(stream_info.max_block_size);
+            if stream_info.min_block_size == stream_info.max_block_size && block_size > 0 {
+                let frame_count = stream_info.total_samples.div_ceil(block_size);
+                let min_frame_size = u64::from(stream_info.min_frame_size.max(1));
+
+                metadata.set_truncated((frames.len() as u64) < frame_count * min_frame_size);
+            }
+        } else if let Block::VorbisComment(comment) = block {
+            metadata.set_vorbis_comments(&comment.comments);
+            // the vendor string names the library that wrote the comments,
+            // which is the encoder's unless a tagger rewrote them since
+            if metadata.encoder.is_none() && !comment.vendor_string.is_empty() {
+                metadata.encoder = Some(comment.vendor_string.clone());
+            }
+        }
+    }
+
+    if let Some(cover) = flac_cover(&tag) {
+        metadata.check_cover_size(Some(cover.data.len()));
+
+        // FLAC stores the dimensions in the block, but not every encoder fills
+        // them in
+        metadata.set_cover_dimensions(if cover.width > 0 && cover.height > 0 {
+            Some((cover.width, cover.height))
+        } else {
+            picture::dimensions(&cover.data)
+        });
+    } else {
+        let cover = flac_application_images(&tag).next();
+        metadata.check_cover_size(cover.map(<[u8]>::len));
+        metadata.set_cover_dimensions(cover.and_then(picture::dimensions));
+    }
+
+    Some(metadata)
+}
+
+/// The stream parameters of an Ogg file, from its identification header and
+/// the granule position of its last page.
+fn read_ogg_stream(reader: &[u8]) -> Option<Metadata> {
+    use ogg_metadata::{read_format, AudioMetadata, OggFormat};
+
+    fn format_metadata<T: AudioMetadata>(metadata: &T, format: Format) -> Metadata {
+        Metadata {
+            channels: Some(metadata.get_output_channel_count().into()),
+            seconds: metadata
+                .get_duration()
+                .map(|duration| duration.as_secs_f64()),
+            ..Metadata::empty(format)
+        }
+    }
+
+    // a page header, with a single entry segment table
+    if reader.len() < 28 {
+        return None;
+    }
+
+    let mut metadata = read_format(Cursor::new(reader)).ok().and_then(|formats| {
+        formats.iter().find_map(|format| match format {
+            OggFormat::Opus(res) => Some(format_metadata(res, Format::Opus)),
+            OggFormat::Vorbis(res) => Some(format_metadata(res, Format::Vorbis)),
+            _ => None,
+        })
+    })?;
+
+    // `ogg_metadata` either gives up on streams without a proper end granule,
+    // or reads the "no granule" marker of the last page as a huge position, so
+    // walk back to the last page that has one, and only fall back to its value
+    metadata.seconds = ogg::duration(reader).or(metadata.seconds);
+
+    // a Q7.8 fixed-point number of dB, after the version, channel count,
+    // pre-skip and input sample rate
+    metadata.output_gain_db = ogg::packets(reader, 1)
+        .first()
+        .filter(|head| head.starts_with(b"OpusHead"))
+        .and_then(|head| head.get(16..18))
+        .map(|gain| f64::from(i16::from_le_bytes([gain[0], gain[1]])) / 256.0);
+
+    Some(metadata)
+}
+
+pub fn read_ogg(reader: &[u8]) -> Option<Metadata> {
+    let mut metadata = read_ogg_stream(reader)?;
+
+    let links = ogg::links(reader);
+    if links.len() > 1 {
+        metadata.warn(format!(
+            "the file chains {} Ogg streams, whose durations are summed, but only the first one's tags are read",
+            links.len()
+        ));
+    }
+    if let Some(link) = links.first().filter(|link| link.len() > 1) {
+        metadata.warn(format!(
+            "the file multiplexes {} logical streams, of which only the first audio stream is read",
+            link.len()
+        ));
+    }
+
+    // the comment header is always the second packet, after the identification header
+    if let Some(comments) = ogg::packets(reader, 2)
+        .get(1)
+        .and_then(|packet| ogg::comments(packet))
+    {
+        metadata.set_vorbis_comments(&comments);
+    }
+
+    Some(metadata)
+}
+
+/// The duration of a fragmented file, whose `moov` sample tables are empty:
+/// from the segment index if there is one, or else the sample durations of the
+/// fragments, or failing that, the fragment duration of the `mehd` box.
+fn mp4_fragmented_seconds(
+    data: &[u8],
+    ctx: &mp4parse::MediaContext,
+    track: &mp4parse::Track,
+) -> Option<f64> {
+    if !atoms::is_fragmented(data) {
+        return None;
+    }
+
+    atoms::sidx_seconds(data, track.track_id)
+        .or_else(|| {
+            let duration = atoms::fragments_duration(data, track.track_id?)?;
+            let timescale = track.timescale?.0;
+            (timescale != 0).then(|| duration as f64 / timescale as f64)
+        })
+        .or_else(|| {
+            let duration = ctx.mvex.as_ref()?.fragment_duration?.0;
+            let timescale = ctx.timescale?.0;
+            (timescale != 0).then(|| duration as f64 / timescale as f64)
+        })
+}
+
+/// The duration of the track's edit list, which leaves out the encoder delay
+/// and padding (e.g. AAC priming) that the media duration counts. Edits are in
+/// the movie's timescale, rather than the track's.
+fn mp4_presentation_seconds(ctx: &mp4parse::MediaContext, track: &mp4parse::Track) -> Option<f64> {
+    let edited = track.edited_duration?.0;
+    let timescale = ctx.timescale?.0;
+
+    // a zero duration edit spans the whole media
+    if edited == 0 || timescale == 0 {
+        return None;
+    }
+
+    Some(edited as f64 / timescale as f64)
+}
+
+pub fn read_mp4(reader: &[u8]) -> Option<Metadata> {
+    use mp4parse::{
+        read_mp4, AudioCodecSpecific, AudioSampleEntry, CodecType, SampleDescriptionBox,
+        SampleEntry, Track, TrackType,
+    };
+
+    // a single box header
+    if reader.len() < 8 {
+        return None;
+    }
+
+    let truncated = atoms::Atoms::new(reader).any(|atom| atom.truncated);
+    let ctx = match read_mp4(&mut { reader }) {
+        Ok(ctx) => ctx,
+        // a DASH media segment, whose tracks are only described by the
+        // initialization segment
+        Err(mp4parse::Error::MoovMissing) if atoms::is_fragmented(reader) => {
+            let mut metadata = Metadata {
+                seconds: atoms::sidx_seconds(reader, None),
+                ..Metadata::empty(Format::Unknown)
+            };
+            metadata.set_truncated(truncated);
+
+            return Some(metadata);
+        }
+        Err(_) => return None,
+    };
+    let cover = mp4_cover(&ctx);
+
+    let mut metadata = ctx
+        .tracks
+        .iter()
+        .filter(|Track { track_type, .. }| track_type == &TrackType::Audio)
+        .filter_map(|track @ Track { stsd, .. }| stsd.as_ref().map(|stsd| (track, stsd)))
+        .find_map(|(track, SampleDescriptionBox { descriptions, .. })| {
+            descriptions.iter().find_map(|entry| match entry {
+                SampleEntry::Audio(entry) => Some((track, entry)),
+                _ => None,
+            })
+        })
+        .map(
+            |(
+                track,
+                &AudioSampleEntry {
+                    codec_type,
+                    channelcount,
+                    samplesize,
+                    samplerate,
+                    ref codec_specific,
+                    ..
+                },
+            )| {
+                let format = match codec_type {
+                    CodecType::MP3 => Format::Mp3,
+                    CodecType::AAC => Format::Aac,
+                    CodecType::ALAC => Format::Alac,
+                    CodecType::AV1 => Format::Av1,
+                    CodecType::Opus => Format::Opus,
+                    CodecType::FLAC => Format::Flac,
+                    CodecType::VP8 => Format::Vp8,
+                    CodecType::VP9 => Format::Vp9,
+                    // the stream parameters are still worth having
+                    _ => Format::Unknown,
+                };
+
+                let seconds = mp4_fragmented_seconds(reader, &ctx, track)
+                    .or_else(|| mp4_presentation_seconds(&ctx, track))
+                    .or_else(|| {
+                        track.duration.and_then(|duration| {
+                            track
+                                .timescale
+                                .map(|timescale| duration.0 as f64 / timescale.0 as f64)
+                        })
+                    });
+
+                let sample_sizes = mp4_sample_sizes(track);
+                let bitrate_mode = match codec_type {
+                    CodecType::ALAC | CodecType::FLAC => Some(BitrateMode::Lossless),
+                    _ => sample_sizes.map(|(_, _, constant)| {
+                        if constant {
+                            BitrateMode::Cbr
+                        } else {
+                            BitrateMode::Vbr
+                        }
+                    }),
+                };
+
+                // encoders fill the sample entry's rate in with the container's,
+                // which for HE-AAC may be either the core rate or the doubled one
+                let max_bitrate = match codec_specific {
+                    AudioCodecSpecific::ES_Descriptor(esds) => esds_max_bitrate(&esds.codec_esds),
+                    _ => None,
+                };
+
+                let samplerate = match codec_specific {
+                    AudioCodecSpecific::ES_Descriptor(esds) if codec_type == CodecType::AAC => {
+                        aac_sample_rate(&esds.decoder_specific_data).map_or(samplerate, f64::from)
+                    }
+                    _ => samplerate,
+                };
+
+                Metadata {
+                    bitrate: sample_sizes
+                        .zip(seconds)
+                        .map(|((_, total, _), seconds)| total as f64 * 8_f64 / seconds / 1_000_f64)
+                        .filter(|bitrate| bitrate.is_finite()),
+                    frame_count: sample_sizes.map(|(count, _, _)| count),
+                    max_bitrate,
+                    bitrate_mode,
+                    channels: Some(channelcount),
+                    sample_rate: Some(samplerate),
+                    bit_depth: Some(samplesize),
+                    seconds,
+                    ..Metadata::empty(format)
+                }
+            },
+        )?;
+
+    if let Some((major_brand, compatible_brands)) = atoms::brands(reader) {
+        metadata.major_brand = Some(major_brand);
+        metadata.compatible_brands = Some(compatible_brands);
+    }
+
+    let freeform = |name| {
+        atoms::ilst(reader)
+            .and_then(|ilst| atoms::freeform(ilst, "com.apple.iTunes", name))
+            .and_then(|value| std::str::from_utf8(value).ok())
+    };
+
+    metadata.sound_check = freeform("iTunNORM").and_then(parse_sound_check);
+    metadata.disc_subtitle = freeform("DISCSUBTITLE").map(String::from);
+
+    let position = |name| {
+        atoms::ilst(reader)
+            .and_then(|ilst| atoms::item(ilst, name))
+            .map_or((None, None), mp4_position)
+    };
+    (metadata.track_number, metadata.total_tracks) = position(b"trkn");
+    (metadata.disc_number, metadata.total_discs) = position(b"disk");
+
+    if let Some(meta) = mp4_meta(&ctx) {
+        let string = |value: &Option<mp4parse::TryString>| {
+            value
+                .as_ref()
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+
+        metadata.artist = string(&meta.artist);
+        metadata.album = string(&meta.album);
+        metadata.title = string(&meta.title);
+        metadata.comment = string(&meta.comment);
+        metadata.genre = match &meta.genre {
+            Some(mp4parse::Genre::CustomGenre(genre)) => {
+                Some(String::from_utf8_lossy(genre).into_owned())
+            }
+            // `gnre` numbers the ID3v1 genres from one
+            Some(mp4parse::Genre::StandardGenre(number)) => {
+                number.checked_sub(1).and_then(|genre_id| {
+                    id3::v1::Tag {
+                        genre_id,
+                        ..id3::v1::Tag::new()
+                    }
+                    .genre()
+                    .map(String::from)
+                })
+            }
+            None => None,
+        };
+        metadata.date = string(&meta.year).map(|date| parse_date(&date));
+        metadata.podcast = meta.podcast;
+        metadata.podcast_url = string(&meta.podcast_url);
+        metadata.episode_guid = string(&meta.podcast_guid);
+        metadata.podcast_category = string(&meta.category);
+        metadata.gapless = meta.gapless_playback;
+        metadata.media_type = meta.media_type.as_ref().and_then(|media_type| {
+            use mp4parse::MediaType;
+
+            media_type_name(match media_type {
+                MediaType::Movie => 0,
+                MediaType::Normal => 1,
+                MediaType::AudioBook => 2,
+                MediaType::WhackedBookmark => 5,
+                MediaType::MusicVideo => 6,
+                MediaType::ShortFilm => 9,
+                MediaType::TVShow => 10,
+                MediaType::Booklet => 11,
+                MediaType::Unknown(stik) => *stik,
+            })
+            .map(String::from)
+        });
+    }
+
+    metadata.check_cover_size(cover.map(<[u8]>::len));
+    metadata.set_cover_dimensions(cover.and_then(picture::dimensions));
+    metadata.set_truncated(truncated);
+
+    let mdats = atoms::Atoms::new(reader)
+        .filter(|atom| &atom.name == b"mdat")
+        .map(|atom| atom.data)
+        .collect::<Vec<_>>();
+    metadata.audio_md5 = audio_md5(mdats.iter().copied());
+    metadata.audio_range = mdats.first().zip(mdats.last()).map(|(first, last)| {
+        let (start, _) = region_range(reader, first);
+        let (_, end) = region_range(reader, last);
+        (start, end)
+    });
+
+    Some(metadata)
+}
+
+/// The number of a track's samples, their total size and whether they're all
+/// the same size, from its `stsz`. Fragmented files leave it empty, describing
+/// their samples in each fragment instead.
+fn mp4_sample_sizes(track: &mp4parse::Track) -> Option<(u64, u64, bool)> {
+    let stsz = track.stsz.as_ref()?;
+
+    // a size for every sample, or else just the one
+    if stsz.sample_size == 0 {
+        let first = *stsz.sample_sizes.first()?;
+        let total = stsz.sample_sizes.iter().copied().map(u64::from).sum();
+        let constant = stsz.sample_sizes.iter().all(|&size| size == first);
+
+        return Some((stsz.sample_sizes.len() as u64, total, constant));
+    }
+
+    let count = track
+        .stts
+        .as_ref()?
+        .samples
+        .iter()
+        .map(|sample| u64::from(sample.sample_count))
+        .sum::<u64>();
+
+    Some((count, u64::from(stsz.sample_size) * count, true)).filter(|&(count, _, _)| count > 0)
+}
+
+/// The peak bitrate an `esds` box's DecoderConfigDescriptor declares, in kbps.
+fn esds_max_bitrate(esds: &[u8]) -> Option<f64> {
+    use std::convert::TryInto;
+
+    // a tag, then the size in up to four 7 bit groups, the high bit marking
+    // all but the last
+    fn descriptor(data: &[u8], tag: u8) -> Option<&[u8]> {
+        let (&found, mut rest) = data.split_first()?;
+        if found != tag {
+            return None;
+        }
+
+        let mut size = 0;
+        for _ in 0..4 {
+            let (&byte, tail) = rest.split_first()?;
+            rest = tail;
+            size = size << 7 | usize::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        rest.get(..size)
+    }
+
+    // the ES_Descriptor's ID, then its flags for the optional fields
+    let es = descriptor(esds, 0x03)?;
+    let flags = *es.get(2)?;
+    let mut offset = 3;
+    if flags & 0x80 != 0 {
+        offset += 2;
+    }
+    if flags & 0x40 != 0 {
+        offset += 1 + usize::from(*es.get(offset)?);
+    }
+    if flags & 0x20 != 0 {
+        offset += 2;
+    }
+
+    // after the object type, stream type and buffer size
+    let config = descriptor(es.get(offset..)?, 0x04)?;
+    let max_bitrate = u32::from_be_bytes(config.get(5..9)?.try_into().ok()?);
+
+    Some(f64::from(max_bitrate) / 1_000_f64).filter(|&bitrate| bitrate > 0_f64)
+}
+
+/// The sampling frequency of an AAC `AudioSpecificConfig`, which for HE-AAC is
+/// that of the core, before SBR doubles it.
+fn aac_sample_rate(config: &[u8]) -> Option<u32> {
+    const SAMPLE_RATES: [u32; 13] = [
+        96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025,
+        8_000, 7_350,
+    ];
+
+    let mut bytes = [0; 8];
+    let len = config.len().min(bytes.len());
+    bytes[..len].copy_from_slice(&config[..len]);
+    let bits = u64::from_be_bytes(bytes);
+
+    let field = |start: usize, bit_len: usize| {
+        (start + bit_len <= len * 8)
+            .then(|| (bits >> (64 - start - bit_len)) as u32 & ((1 << bit_len) - 1))
+    };
+
+    // object types past 30 take another 6 bits
+    let start = if field(0, 5)? == 31 { 11 } else { 5 };
+
+    match field(start, 4)? {
+        // a rate that isn't one of the standard ones, spelled out
+        0xF => field(start + 4, 24).filter(|&rate| rate > 0),
+        index => SAMPLE_RATES.get(index as usize).copied(),
+    }
+}
+
+/// Reads the `(language, title)` of every localized title of an MP4 file, from
+/// the `©nam` item's language-tagged `data` atoms and the QuickTime `©nam`
+/// user data atom. Languages are ISO 639-2 codes, and `und` for the default
+/// title. Returns `None` for files that aren't MP4.
+pub fn read_mp4_localized_titles(data: &[u8]) -> Option<Vec<(String, String)>> {
+    atoms::child(data, b"moov")?;
+
+    Some(atoms::localized_texts(data, b"\xA9nam"))
+}
+
+/// The iTunes Store purchase items of an MP4 file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PurchaseInfo {
+    /// The Apple ID of the account that bought the file, from `apID`.
+    pub account: Option<String>,
+    /// The store's ID of the purchased content, from `cnID`.
+    pub content_id: Option<u32>,
+    /// The purchase date as the store wrote it, e.g. `2021-06-15 08:00:00`,
+    /// from `purd`.
+    pub purchase_date: Option<String>,
+}
+
+/// Reads the iTunes Store purchase items of an MP4 file. Returns `None` for
+/// files that aren't MP4, or that have none of the items.
+pub fn read_mp4_purchase_info(data: &[u8]) -> Option<PurchaseInfo> {
+    use std::convert::TryInto;
+
+    let ilst = atoms::ilst(data)?;
+    let text =
+        |name| atoms::item(ilst, name).map(|value| String::from_utf8_lossy(value).into_owned());
+
+    let info = PurchaseInfo {
+        account: text(b"apID"),
+        content_id: atoms::item(ilst, b"cnID")
+            .and_then(|value| Some(u32::from_be_bytes(value.try_into().ok()?))),
+        purchase_date: text(b"purd"),
+    };
+
+    if info == PurchaseInfo::default() {
+        None
+    } else {
+        Some(info)
+    }
+}
+
+/// The stream parameters of a WAV file, from its `fmt ` and `data` chunks.
+fn read_wav_stream(reader: &[u8]) -> Option<Metadata> {
+    use hound::{WavReader, WavSpec};
+    use riff::{channel_layout, read_fmt, Chunks, WAVE_FORMAT_IEEE_FLOAT, WAVE_FORMAT_PCM};
+
+    // the RIFF header, followed by a PCM `fmt ` chunk
+    if reader.len() < 36 {
+        return None;
+    }
+
+    let data = reader;
+    let reader = match WavReader::new(reader) {
+        Ok(reader) => reader,
+        // `hound` only reads PCM and float samples
+        Err(_)
+            if read_fmt(data).is_some_and(|fmt| {
+                !matches!(
+                    fmt.effective_format_tag(),
+                    WAVE_FORMAT_PCM | WAVE_FORMAT_IEEE_FLOAT
+                )
+            }) =>
+        {
+            return read_compressed_wav(data);
+        }
+        // the RIFF/WAVE header matched, so this is still a WAV file
+        Err(err) if Chunks::new(data).is_some() => {
+            let mut metadata = Metadata::empty(Format::Wav);
+            metadata.warn(format!("failed to read the WAV header: {}", err));
+            return Some(metadata);
+        }
+        Err(_) => return None,
+    };
+
+    let WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        ..
+    } = reader.spec();
+
+    // `hound` reports the valid bits of extensible files as `bits_per_sample`,
+    // so go to the `fmt ` chunk for the container width
+    let fmt = read_fmt(data);
+    let bits_per_sample = fmt
+        .as_ref()
+        .map_or(bits_per_sample, |fmt| fmt.bits_per_sample);
+
+    let truncated = Chunks::new(data)?
+        .find(|chunk| &chunk.id == b"data")
+        .is_none_or(|chunk| chunk.truncated);
+
+    let mut metadata = Metadata {
+        seconds: Some(f64::from(reader.duration()) / f64::from(sample_rate)),
+        sample_rate: Some(sample_rate.into()),
+        bit_depth: Some(bits_per_sample),
+        valid_bit_depth: fmt
+            .as_ref()
+            .and_then(|fmt| fmt.extensible.as_ref())
+            .map(|extensible| extensible.valid_bits_per_sample)
+            .filter(|&valid_bits| valid_bits > 0),
+        sample_format: fmt
+            .as_ref()
+            .and_then(|fmt| match fmt.effective_format_tag() {
+                WAVE_FORMAT_PCM => Some("int"),
+                WAVE_FORMAT_IEEE_FLOAT => Some("float"),
+                _ => None,
+            })
+            .map(String::from),
+        channels: Some(channels.into()),
+        channel_mask: fmt
+            .as_ref()
+            .and_then(|fmt| fmt.extensible.as_ref())
+            .map(|extensible| extensible.channel_mask),
+        channel_layout: fmt
+            .as_ref()
+            .and_then(|fmt| fmt.extensible.as_ref())
+            .and_then(|extensible| channel_layout(extensible.channel_mask)),
+        bitrate: Some(
+            f64::from(sample_rate) * f64::from(channels) * f64::from(bits_per_sample) / 1_000_f64,
+        ),
+        // `hound` only reads uncompressed PCM
+        bitrate_mode: Some(BitrateMode::Lossless),
+        ..Metadata::empty(Format::Wav)
+    };
+
+    if let Some(stream) = wav_coded_stream(data) {
+        // the `fmt ` chunk describes the PCM carrying the stream, except for
+        // how long it plays
+        metadata = Metadata {
+            seconds: metadata.seconds,
+            sample_rate: Some(stream.sample_rate.into()),
+            sample_format: Some(String::from("compressed")),
+            channels: Some(stream.channels),
+            bitrate: stream.bitrate,
+            bitrate_mode: Some(BitrateMode::Cbr),
+            ..Metadata::empty(stream_format(&stream))
+        };
+    }
+
+    metadata.set_truncated(truncated);
+
+    Some(metadata)
+}
+
+/// The DTS or AC-3 stream a 16-bit PCM WAV file holds in place of samples, as
+/// DTS CDs and S/PDIF captures do.
+fn wav_coded_stream(data: &[u8]) -> Option<surround::Stream> {
+    let fmt = riff::read_fmt(data)?;
+    if fmt.effective_format_tag() != riff::WAVE_FORMAT_PCM || fmt.bits_per_sample != 16 {
+        return None;
+    }
+
+    let chunk = riff::Chunks::new(data)?.find(|chunk| &chunk.id == b"data")?;
+    surround::find(chunk.data)
+}
+
+fn stream_format(stream: &surround::Stream) -> Format {
+    match stream.codec {
+        surround::Codec::Dts => Format::Dts,
+        surround::Codec::Ac3 => Format::Ac3,
+    }
+}
+
+/// The stream parameters of a WAV file in a compressed codec like µ-law or
+/// ADPCM, from its `fmt `, `fact` and `data` chunks.
+fn read_compressed_wav(data: &[u8]) -> Option<Metadata> {
+    use riff::{codec_name, read_fmt, Chunks};
+    use std::convert::TryInto;
+
+    let fmt = read_fmt(data)?;
+    let mut fact = None;
+    let mut truncated = true;
+    let mut data_len = None;
+
+    for chunk in Chunks::new(data)? {
+        match &chunk.id {
+            // the length in samples per channel, which compressed files need
+            // since their block sizes don't map to samples
+            b"fact" => {
+                fact = chunk
+                    .data
+                    .get(..4)
+                    .and_then(|len| len.try_into().ok())
+                    .map(u32::from_le_bytes);
+            }
+            b"data" => {
+                truncated = chunk.truncated;
+                data_len = Some(chunk.data.len());
+            }
+            _ => {}
+        }
+    }
+
+    let seconds = match (fact, data_len) {
+        (Some(samples), _) if fmt.sample_rate > 0 => {
+            Some(f64::from(samples) / f64::from(fmt.sample_rate))
+        }
+        (_, Some(len)) if fmt.byte_rate > 0 => Some(len as f64 / f64::from(fmt.byte_rate)),
+        _ => None,
+    };
+
+    let mut metadata = Metadata {
+        seconds,
+        channels: Some(fmt.channels.into()),
+        sample_rate: Some(fmt.sample_rate.into()),
+        bit_depth: Some(fmt.bits_per_sample).filter(|&bits| bits > 0),
+        sample_format: Some(String::from(codec_name(fmt.effective_format_tag()))),
+        bitrate: Some(f64::from(fmt.byte_rate) * 8_f64 / 1_000_f64).filter(|&rate| rate > 0_f64),
+        ..Metadata::empty(Format::Wav)
+    };
+
+    metadata.set_truncated(truncated);
+
+    Some(metadata)
+}
+
+pub fn read_wav(reader: &[u8]) -> Option<Metadata> {
+    use riff::{info_text, Chunks};
+
+    let data = reader;
+    let mut metadata = read_wav_stream(data)?;
+
+    // `hound` stops at the `data` chunk, but DJ software often appends its
+    // metadata after it
+    let mut extra = HashMap::new();
+    let mut id3 = None;
+    let mut track = None;
+
+    for chunk in Chunks::new(data)? {
+        match &chunk.id {
+            b"LIST" => {
+                for field in Chunks::list(chunk.data, b"INFO").into_iter().flatten() {
+                    let value = match info_text(field.data) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+
+                    let target = match &field.id {
+                        b"INAM" => &mut metadata.title,
+                        b"IART" => &mut metadata.artist,
+                        b"IPRD" => &mut metadata.album,
+                        b"ICMT" => &mut metadata.comment,
+                        b"IGNR" => &mut metadata.genre,
+                        b"ICRD" => &mut metadata.date,
+                        // `ITRK` is the usual field for the track, and some
+                        // software writes `IPRT`, for the part, instead
+                        b"ITRK" => {
+                            track = Some(value);
+                            continue;
+                        }
+                        b"IPRT" => {
+                            track.get_or_insert(value);
+                            continue;
+                        }
+                        id => {
+                            if let Some((_, name)) = RIFF_INFO_EXTRA_FIELDS
+                                .iter()
+                                .find(|(field, _)| *field == id)
+                            {
+                                extra.entry(String::from(*name)).or_insert(value);
+                            }
+                            continue;
+                        }
+                    };
+
+                    target.get_or_insert(value);
+                }
+            }
+            b"id3 " | b"ID3 " => id3 = id3::Tag::read_from(chunk.data).ok(),
+            _ => {}
+        }
+    }
+
+    (metadata.track_number, metadata.total_tracks) =
+        track.as_deref().map_or((None, None), parse_position);
+
+    if let Some(chunk) = Chunks::new(data)?.find(|chunk| &chunk.id == b"data") {
+        metadata.audio_md5 = audio_md5([chunk.data]);
+        metadata.audio_range = Some(region_range(data, chunk.data));
+    }
+
+    // an embedded ID3 tag only fills in what `INFO` didn't have
+    if let Some(tag) = id3 {
+        use id3::TagLike;
+
+        let genre = id3_genre(&tag);
+        let original_artist = id3_original_artist(&tag);
+        let fields = [
+            (&mut metadata.title, tag.title()),
+            (&mut metadata.artist, tag.artist()),
+            (&mut metadata.album, tag.album()),
+            (&mut metadata.comment, id3_comment(&tag)),
+            (&mut metadata.genre, genre.as_deref()),
+            (&mut metadata.date, id3_date(&tag)),
+            (&mut metadata.disc_subtitle, id3_disc_subtitle(&tag)),
+            (&mut metadata.original_artist, original_artist.as_deref()),
+            (&mut metadata.original_album, id3_text(&tag, "TOAL")),
+        ];
+
+        for (target, value) in fields {
+            if target.is_none() {
+                *target = value.map(String::from);
+            }
+        }
+
+        metadata.original_year = id3_original_year(&tag);
+
+        for (key, value) in id3_extra(&tag) {
+            extra.entry(key).or_insert(value);
+        }
+    }
+
+    metadata.set_extra(extra);
+
+    Some(metadata)
+}
+
+/// Reads the `(sample_position, label)` markers of a WAV file, from its `cue `
+/// chunk and the `labl` entries of its `LIST adtl` chunk. Cue points without a
+/// label get an empty one.
+pub fn read_wav_markers(data: &[u8]) -> Option<Vec<(u32, String)>> {
+    use riff::{info_text, Chunks};
+    use std::convert::TryInto;
+
+    let u32_le = |data: &[u8], offset: usize| {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+
+    let mut cue_points = None;
+    let mut labels = HashMap::new();
+
+    for chunk in Chunks::new(data)? {
+        match &chunk.id {
+            b"cue " => {
+                // the point count, then 24 bytes per point: its ID, its playlist
+                // position, the chunk it's in, the chunk and block offsets, and
+                // the sample offset
+                let count = u32_le(chunk.data, 0)? as usize;
+                let points = chunk.data.get(4..)?.chunks_exact(24).take(count);
+
+                cue_points = Some(
+                    points
+                        .filter_map(|point| Some((u32_le(point, 0)?, u32_le(point, 20)?)))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            b"LIST" => {
+                for entry in Chunks::list(chunk.data, b"adtl").into_iter().flatten() {
+                    if &entry.id != b"labl" {
+                        continue;
+                    }
+
+                    // the cue point ID, then a null-terminated label
+                    let label = entry.data.get(4..).and_then(info_text);
+                    if let (Some(id), Some(label)) = (u32_le(entry.data, 0), label) {
+                        labels.insert(id, label);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let markers = cue_points?
+        .into_iter()
+        .map(|(id, position)| (position, labels.remove(&id).unwrap_or_default()))
+        .collect();
+
+    Some(markers)
+}
+
+/// Reads the XML of the `iXML` chunk field recorders write their scene, take
+/// and track names to, as it is. Returns `None` for files that aren't WAV, or
+/// don't have one.
+pub fn read_wav_ixml(data: &[u8]) -> Option<String> {
+    let ixml = riff::Chunks::new(data)?
+        .find(|chunk| &chunk.id == b"iXML")?
+        .data;
+
+    // some writers pad the XML with nulls, to leave room for later edits
+    let xml = String::from_utf8_lossy(ixml);
+    Some(String::from(xml.trim_end_matches('\0')))
+}
+
+/// The Broadcast Wave (EBU Tech 3285) `bext` chunk of a WAV file. Its text
+/// fields are null-padded ASCII, missing when they're blank.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BextInfo {
+    pub description: Option<String>,
+    pub originator: Option<String>,
+    pub originator_reference: Option<String>,
+    /// As `yyyy-mm-dd`.
+    pub origination_date: Option<String>,
+    /// As `hh:mm:ss`, though some writers separate the fields with dashes.
+    pub origination_time: Option<String>,
+    /// The timecode of the first sample, as the number of samples since
+    /// midnight.
+    pub time_reference: u64,
+    /// `time_reference` in seconds, at the sample rate of the `fmt ` chunk.
+    pub time_reference_seconds: Option<f64>,
+    pub version: u16,
+    /// The coding history, a line for each process the audio went through.
+    pub coding_history: Option<String>,
+}
+
+/// Reads the Broadcast Wave `bext` chunk of a WAV file. Returns `None` for files
+/// that aren't WAV, or don't have one.
+pub fn read_wav_bext(data: &[u8]) -> Option<BextInfo> {
+    use riff::{info_text, read_fmt, Chunks};
+    use std::convert::TryInto;
+
+    let bext = Chunks::new(data)?.find(|chunk| &chunk.id == b"bext")?.data;
+
+    let text = |range: std::ops::Range<usize>| {
+        let field = bext.get(range)?;
+        info_text(field.split(|&byte| byte == 0).next()?)
+    };
+
+    // a 64-bit count split in two 32-bit halves, the low one first
+    let time_reference = bext.get(338..346)?;
+    let time_reference = u64::from(u32::from_le_bytes(time_reference[..4].try_into().ok()?))
+        | u64::from(u32::from_le_bytes(time_reference[4..].try_into().ok()?)) << 32;
+
+    let sample_rate = read_fmt(data)
+        .map(|fmt| fmt.sample_rate)
+        .filter(|&rate| rate > 0);
+
+    Some(BextInfo {
+        description: text(0..256),
+        originator: text(256..288),
+        originator_reference: text(288..320),
+        origination_date: text(320..330),
+        origination_time: text(330..338),
+        time_reference,
+        time_reference_seconds: sample_rate.map(|rate| time_reference as f64 / f64::from(rate)),
+        version: bext
+            .get(346..348)
+            .map_or(0, |version| u16::from_le_bytes([version[0], version[1]])),
+        // after the UMID, the loudness values and the reserved bytes
+        coding_history: text(602..bext.len()),
+    })
+}
+
+/// Reads the tags of a file as the `(key, value)` pairs it stores them as, in
+/// order: Vorbis comments, MP4 item atoms (`©nam`, or the `name` of freeform
+/// items), ID3v2 text frames (`TIT2`, or the description of `TXXX` frames) and
+/// RIFF `INFO` fields. With `normalize_keys`, keys are lowercased and stripped
+/// of separators the way `extra` keys are, so `ALBUM ARTIST` and
+/// `AlbumArtist` both read as `albumartist`.
+pub fn read_raw_tags(data: &[u8], normalize_keys: bool) -> Option<Vec<(String, String)>> {
+    let tags = match detect_format(data)? {
+        "FLAC" => {
+            let tag = metaflac::Tag::read_from(&mut { data }).ok()?;
+            let comment = tag.vorbis_comments()?;
+
+            comment
+                .comments
+                .iter()
+                .flat_map(|(key, values)| {
+                    values.iter().map(move |value| (key.clone(), value.clone()))
+                })
+                .collect()
+        }
+        "Ogg" => ogg::raw_comments(ogg::packets(data, 2).get(1)?)?,
+        "MP4" => atoms::text_items(atoms::ilst(data)?),
+        "WAV" => riff::Chunks::new(data)?
+            .filter(|chunk| &chunk.id == b"LIST")
+            .flat_map(|chunk| {
+                riff::Chunks::list(chunk.data, b"INFO")
+                    .into_iter()
+                    .flatten()
+            })
+            .filter_map(|field| {
+                let key = String::from_utf8_lossy(&field.id).into_owned();
+                Some((key, riff::info_text(field.data)?))
+            })
+            .collect(),
+        "MP3" => {
+            let tag = id3::Tag::read_from(data).ok()?;
+
+            tag.frames()
+                .filter_map(|frame| {
+                    let (key, text) = match frame.content() {
+                        id3::Content::ExtendedText(text) => {
+                            (text.description.clone(), text.value.clone())
+                        }
+                        content => (
+                            String::from(frame.id()),
+                            content.text_values()?.collect::<Vec<_>>().join(", "),
+                        ),
+                    };
+
+                    Some((key, text))
+                })
+                .collect()
+        }
+        _ => return None,
+    };
+
+    if !normalize_keys {
+        return Some(tags);
+    }
+
+    let tags = tags
+        .into_iter()
+        .map(|(key, value)| (normalize_tag_key(&key), value))
+        .collect();
+
+    Some(tags)
+}
+
+pub fn read_amr(reader: &[u8]) -> Option<Metadata> {
+    // frame sizes in bytes, header included, indexed by frame type
+    const NB_FRAME_SIZES: [usize; 16] = [13, 14, 16, 18, 20, 21, 27, 32, 6, 1, 1, 1, 1, 1, 1, 1];
+    const WB_FRAME_SIZES: [usize; 16] = [18, 24, 33, 37, 41, 47, 51, 59, 61, 6, 1, 1, 1, 1, 1, 1];
+
+    let (frames, sample_rate, frame_sizes) = if let Some(frames) = reader.strip_prefix(b"#!AMR\n") {
+        (frames, 8_000, &NB_FRAME_SIZES)
+    } else if let Some(frames) = reader.strip_prefix(b"#!AMR-WB\n") {
+        (frames, 16_000, &WB_FRAME_SIZES)
+    } else {
+        return None;
+    };
+
+    let mut frame_count = 0_u32;
+    let mut pos = 0;
+    while let Some(&header) = frames.get(pos) {
+        pos += frame_sizes[usize::from((header >> 3) & 0x0F)];
+        frame_count += 1;
+    }
+
+    // every frame holds 20ms of audio
+    let seconds = f64::from(frame_count) * 0.02;
+
+    Some(Metadata {
+        seconds: Some(seconds),
+        channels: Some(1),
+        sample_rate: Some(sample_rate.into()),
+        bitrate: Some(frames.len() as f64 * 8_f64 / seconds / 1_000_f64)
+            .filter(|bitrate| bitrate.is_finite()),
+        // AMR files have no tags, so that's everything after the magic
+        audio_md5: audio_md5([frames]),
+        audio_range: Some(region_range(reader, frames)),
+        ..Metadata::empty(Format::Amr)
+    })
+}
+
+/// Describes headerless PCM audio, of integer samples in the given format, as
+/// if it had been wrapped in a WAV header.
+pub fn read_raw_pcm(data: &[u8], sample_rate: u32, channels: u32, bit_depth: u16) -> Metadata {
+    // samples are padded out to whole bytes, and interleaved
+    let frame_size = u64::from(bit_depth).div_ceil(8) * u64::from(channels);
+    let bitrate = f64::from(sample_rate) * f64::from(channels) * f64::from(bit_depth) / 1_000_f64;
+
+    let mut metadata = Metadata {
+        seconds: Some(data.len() as f64 / frame_size as f64 / f64::from(sample_rate))
+            .filter(|seconds| seconds.is_finite()),
+        sample_rate: Some(sample_rate.into()),
+        bit_depth: Some(bit_depth),
+        sample_format: Some(String::from("int")),
+        channels: Some(channels),
+        bitrate: Some(bitrate),
+        bitrate_mode: Some(BitrateMode::Lossless),
+        audio_md5: audio_md5([data]),
+        audio_range: Some((0, data.len() as u64)),
+        ..Metadata::empty(Format::Wav)
+    };
+
+    if frame_size > 0 && !(data.len() as u64).is_multiple_of(frame_size) {
+        metadata.warn("the audio ends partway through a sample frame");
+    }
+
+    metadata
+}
+
+/// Tells whether a file looks like it's in a reader's format, usually from its
+/// magic, so that the reader isn't run on files it can't handle.
+pub type DetectFn = fn(&[u8]) -> bool;
+
+/// Reads the metadata of a file, or returns `None` if it turns out not to be in
+/// the reader's format after all.
+pub type ReadFn = fn(&[u8]) -> Option<Metadata>;
+
+/// The built-in readers `read_any` tries, in order. MP3 goes last, since it
+/// accepts almost anything.
+const READERS: [(&str, DetectFn, ReadFn); 6] = [
+    // not every MP4 file starts with `ftyp`, but they all start with one of
+    // the top-level boxes
+    (
+        "MP4",
+        |data| {
+            matches!(
+                data.get(4..8),
+                Some(
+                    b"ftyp"
+                        | b"styp"
+                        | b"moov"
+                        | b"moof"
+                        | b"sidx"
+                        | b"mdat"
+                        | b"free"
+                        | b"skip"
+                        | b"wide"
+                        | b"uuid"
+                )
+            )
+        },
+        read_mp4,
+    ),
+    ("Ogg", |data| data.starts_with(b"OggS"), read_ogg),
+    // `metaflac` skips an ID3v2 tag in front of the magic
+    (
+        "FLAC",
+        |data| data[id3v2_len(data)..].starts_with(b"fLaC"),
+        read_flac,
+    ),
+    ("WAV", |data| riff::Chunks::new(data).is_some(), read_wav),
+    ("AMR", |data| data.starts_with(b"#!AMR"), read_amr),
+    // MPEG audio frames have no magic, and can follow any amount of junk
+    ("MP3", |_| true, read_mp3),
+];
+
+/// Readers added with `register_reader`, which are tried before the built-in
+/// ones.
+static REGISTERED_READERS: std::sync::RwLock<Vec<(&str, DetectFn, ReadFn)>> =
+    std::sync::RwLock::new(Vec::new());
+
+/// Adds a reader for another format to `read_any`, and so to `fazer`. It's
+/// tried before the built-in readers (and after the ones registered before
+/// it), on every file that `detect` accepts.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn register_reader(format: &'static str, detect: DetectFn, read: ReadFn) {
+    REGISTERED_READERS
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .push((format, detect, read));
+}
+
+/// Removes the readers registered for `format` with `register_reader`, and
+/// returns whether there were any.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unregister_reader(format: &str) -> bool {
+    let mut registered = REGISTERED_READERS
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    let len = registered.len();
+    registered.retain(|&(registered, _, _)| registered != format);
+    registered.len() < len
+}
+
+/// The readers to try on `data`, in order: the registered readers, then the
+/// built-in ones, skipping those whose format `data` doesn't look like.
+fn readers(data: &[u8]) -> Vec<(&'static str, ReadFn)> {
+    let registered = REGISTERED_READERS
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+    registered
+        .iter()
+        .chain(&READERS)
+        .filter(|(_, detect, _)| detect(data))
+        .map(|&(format, _, read)| (format, read))
+        .collect()
+}
+
+/// Too short for any of the formats' headers, the smallest being AMR's magic.
+const MIN_HEADER_LEN: usize = 6;
+
+/// Runs a single reader, recording a panic in it into `panics` rather than
+/// letting it unwind.
+fn read_catching_panics(
+    format: &str,
+    read: impl Fn(&[u8]) -> Option<Metadata> + std::panic::RefUnwindSafe,
+    data: &[u8],
+    panics: &mut Vec<String>,
+) -> Option<Metadata> {
+    // a panic in one of the parsing crates only rules out its own format. This
+    // can't help on wasm, where panics abort, unless built with `panic=unwind`
+    std::panic::catch_unwind(|| read(data)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+
+        panics.push(format!("the {} reader panicked: {}", format, message));
+        None
+    })
+}
+
+fn with_panics(metadata: Option<Metadata>, panics: Vec<String>) -> Option<Metadata> {
+    metadata.map(|mut metadata| {
+        for warning in panics {
+            metadata.warn(warning);
+        }
+
+        metadata
+    })
+}
+
+/// Reads only the stream parameters of a file: its format, channels, sample
+/// rate, bit depth, duration and bitrate. Text tags and pictures are skipped
+/// wherever the format allows it.
+pub fn read_stream_info(data: &[u8]) -> Option<Metadata> {
+    // MP4 and AMR have no cheaper path
+    const STREAM_READERS: [(&str, ReadFn); 6] = [
+        ("MP4", read_mp4),
+        ("Ogg", read_ogg_stream),
+        ("FLAC", read_flac_stream),
+        ("WAV", read_wav_stream),
+        ("AMR", read_amr),
+        ("MP3", read_mp3_stream),
+    ];
+
+    if data.len() < MIN_HEADER_LEN {
+        return None;
+    }
+
+    let mut panics = Vec::new();
+    let metadata = STREAM_READERS
+        .iter()
+        .find_map(|&(format, read)| read_catching_panics(format, read, data, &mut panics))
+        .map(Metadata::into_stream_info);
+
+    with_panics(metadata, panics)
+}
+
+/// Reads the metadata of a file in any of the supported formats, trying each
+/// reader in turn. This is what the `fazer` wasm export does, and never panics.
+pub fn read_any(data: &[u8]) -> Option<Metadata> {
+    read_detected(data).map(|(_, metadata)| metadata)
+}
+
+/// Like `read_any`, but with `options` applied: the MP3 ones to MP3 files, and
+/// `duration_precision` to every format.
+pub fn read_any_with_options(data: &[u8], options: &ReadOptions) -> Option<Metadata> {
+    try_read_any_with_options(data, options).ok()
+}
+
+/// Like `read_any_with_options`, but says why it failed: the file is in none
+/// of the supported formats, or it's a strict read and fields are missing.
+pub fn try_read_any_with_options(
+    data: &[u8],
+    options: &ReadOptions,
+) -> Result<Metadata, FazerError> {
+    let (_, metadata) =
+        read_detected_with_options(data, options).ok_or(FazerError::UnrecognizedFormat)?;
+
+    if options.strict {
+        let fields = [
+            ("format", metadata.format == Format::Unknown),
+            ("duration", metadata.seconds.is_none()),
+            ("sample rate", metadata.sample_rate.is_none()),
+        ];
+
+        let missing = fields
+            .iter()
+            .filter(|(_, missing)| *missing)
+            .map(|&(field, _)| field)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(FazerError::Incomplete(missing));
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Like `read_any`, but tries the reader `detect_format` picks before the rest.
+pub fn read_best(data: &[u8]) -> Option<Metadata> {
+    read_in_order(data, &[detect_format(data)?])
+}
+
+/// Like `read_any`, but tries the readers named in `order`, as `detect_format`
+/// names them, before the rest, e.g. `["MP3"]` for a library of mostly MP3
+/// files. Readers still only run on files with their format's magic, so this
+/// only changes which one wins for files that several of them claim.
+pub fn read_in_order(data: &[u8], order: &[&str]) -> Option<Metadata> {
+    read_detected_in_order(data, &ReadOptions::default(), order).map(|(_, metadata)| metadata)
+}
+
+/// Like `read_any`, but also returns the name of the reader that succeeded.
+fn read_detected(data: &[u8]) -> Option<(&'static str, Metadata)> {
+    read_detected_with_options(data, &ReadOptions::default())
+}
+
+fn read_detected_with_options(
+    data: &[u8],
+    options: &ReadOptions,
+) -> Option<(&'static str, Metadata)> {
+    read_detected_in_order(data, options, &[])
+}
+
+fn read_detected_in_order(
+    data: &[u8],
+    options: &ReadOptions,
+    order: &[&str],
+) -> Option<(&'static str, Metadata)> {
+    if data.len() < MIN_HEADER_LEN {
+        return None;
+    }
+
+    // a stable sort, so the rest keep their usual order
+    let mut readers = readers(data);
+    readers.sort_by_key(|(format, _)| {
+        order
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(format))
+            .unwrap_or(order.len())
+    });
+
+    let mut panics = Vec::new();
+    let detected = readers.into_iter().find_map(|(format, read)| {
+        let metadata = if format == "MP3" {
+            read_catching_panics(
+                format,
+                |data| read_mp3_with_options(data, options),
+                data,
+                &mut panics,
+            )
+        } else {
+            read_catching_panics(format, read, data, &mut panics)
+        };
+
+        metadata.map(|metadata| (format, metadata))
+    });
+
+    let (format, mut metadata) = detected?;
+    metadata.round_seconds(options.duration_precision);
+    if options.normalize_unicode {
+        metadata.normalize_unicode();
+    }
+
+    Some((format, with_panics(Some(metadata), panics)?))
+}
+
+/// The name of the reader whose format `data` looks like, going by the magic
+/// the readers check for: one of `MP4`, `Ogg`, `FLAC`, `WAV` and `AMR`, or a
+/// registered reader's. MP3 has no magic, so it's what anything else is taken
+/// for.
+pub fn detect_format(data: &[u8]) -> Option<&'static str> {
+    readers(data).first().map(|&(format, _)| format)
+}
+
+/// What `detect` makes of a file, without running any of the readers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectResult {
+    /// The codec, if the container's headers give it away cheaply, or else
+    /// `Format::Unknown`.
+    pub format: Format,
+    /// The name of the reader for the container, as `detect_format` returns it.
+    pub container: &'static str,
+    /// How likely `format` is to be what the reader will report, from 0 to 1:
+    /// 1 for a codec named outright, lower for a good guess, and lower still
+    /// for an unknown codec or a file taken for MP3 for want of anything else.
+    pub confidence: f32,
+}
+
+/// Like `detect_format`, but also peeks at the codec and says how sure it is.
+/// This is cheap, so a low `confidence` is a hint to run the readers, e.g.
+/// with `read_any`, rather than to trust the guess.
+pub fn detect(data: &[u8]) -> Option<DetectResult> {
+    let container = detect_format(data)?;
+
+    let (format, confidence) = match container {
+        "FLAC" => (Format::Flac, 1.0),
+        "WAV" => match wav_coded_stream(data) {
+            Some(stream) => (stream_format(&stream), 1.0),
+            None => (Format::Wav, 1.0),
+        },
+        "AMR" => (Format::Amr, 1.0),
+        // the identification header is the first packet
+        "Ogg" => match ogg::packets(data, 1).first() {
+            Some(packet) if packet.starts_with(b"OpusHead") => (Format::Opus, 1.0),
+            Some(packet) if packet.starts_with(b"\x01vorbis") => (Format::Vorbis, 1.0),
+            _ => (Format::Unknown, 0.5),
+        },
+        "MP4" => match atoms::first_sample_entry(data).as_ref() {
+            // `mp4a` also holds MP3, AC-3 and others, told apart by the `esds`
+            // object type, but AAC is by far the most common
+            Some(b"mp4a") => (Format::Aac, 0.75),
+            Some(b"alac") => (Format::Alac, 1.0),
+            Some(b"Opus") => (Format::Opus, 1.0),
+            Some(b"fLaC") => (Format::Flac, 1.0),
+            Some(b".mp3") => (Format::Mp3, 1.0),
+            Some(b"av01") => (Format::Av1, 1.0),
+            Some(b"vp08") => (Format::Vp8, 1.0),
+            Some(b"vp09") => (Format::Vp9, 1.0),
+            // a fragment, or a `moov` past the end of what was read
+            _ => (Format::Unknown, 0.5),
+        },
+        "MP3" => {
+            let audio = &data[mp3_audio_offset(data)..];
+
+            match audio {
+                // the frame sync, right after the tags
+                [0xFF, second, ..] if second & 0xE0 == 0xE0 => (Format::Mp3, 0.75),
+                _ if audio.len() < data.len() => (Format::Mp3, 0.5),
+                _ => (Format::Mp3, 0.1),
+            }
+        }
+        // a registered reader's, which only it knows the codecs of
+        _ => (Format::Unknown, 0.5),
+    };
+
+    Some(DetectResult {
+        format,
+        container,
+        confidence,
+    })
+}
+
+/// A human-readable outline of the container of `data`, for debugging: the
+/// MP4 box tree, the RIFF chunks, the FLAC metadata blocks or the Ogg pages,
+/// one per line and indented by nesting, each with its size in bytes. `None`
+/// for the formats without one, i.e. MP3 and AMR.
+pub fn describe_structure(data: &[u8]) -> Option<String> {
+    match detect_format(data)? {
+        "MP4" => Some(structure::mp4(data)),
+        "Ogg" => Some(structure::ogg(data)),
+        "FLAC" => Some(structure::flac(data, id3v2_len(data))),
+        "WAV" => structure::riff(data),
+        _ => None,
+    }
+}
+
+/// The file extensions of each built-in reader's format.
+const EXTENSIONS: [(&str, &str); 12] = [
+    ("mp3", "MP3"),
+    ("flac", "FLAC"),
+    ("ogg", "Ogg"),
+    ("oga", "Ogg"),
+    ("opus", "Ogg"),
+    ("m4a", "MP4"),
+    ("m4b", "MP4"),
+    ("mp4", "MP4"),
+    ("m4s", "MP4"),
+    ("wav", "WAV"),
+    ("wave", "WAV"),
+    ("amr", "AMR"),
+];
+
+/// The reader an extension implies, like `mp3` or `.FLAC`.
+fn extension_format(extension: &str) -> Option<&'static str> {
+    let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+
+    EXTENSIONS
+        .iter()
+        .find(|&&(known, _)| known == extension)
+        .map(|&(_, format)| format)
+}
+
+/// Every format the built-in readers can report, other than `Unknown`.
+pub fn supported_formats() -> Vec<Format> {
+    vec![
+        Format::Mp3,
+        Format::Flac,
+        Format::Opus,
+        Format::Vorbis,
+        Format::Aac,
+        Format::Alac,
+        Format::Av1,
+        Format::Vp8,
+        Format::Vp9,
+        Format::Wav,
+        Format::Amr,
+        Format::Dts,
+        Format::Ac3,
+    ]
+}
+
+/// The file extensions the built-in readers handle, without the leading dot,
+/// e.g. for a file picker's accepted types.
+pub fn supported_extensions() -> Vec<&'static str> {
+    EXTENSIONS.iter().map(|&(extension, _)| extension).collect()
+}
+
+/// Like `read_any`, but also checks the file's content against the format its
+/// `extension` implies. The content always wins, and a mismatch is reported as
+/// a warning, e.g. `extension says MP3 but content is FLAC`.
+pub fn read_with_hint(data: &[u8], extension: &str) -> Option<Metadata> {
+    let (format, mut metadata) = read_detected(data)?;
+
+    match extension_format(extension) {
+        Some(implied) if implied != format => {
+            metadata.warn(format!(
+                "extension says {} but content is {}",
+                implied, format
+            ));
+        }
+        _ => {}
+    }
+
+    Some(metadata)
+}
+
+#[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+pub fn read_batch(files: &[(&str, &[u8])]) -> Vec<(String, Option<Metadata>)> {
+    use rayon::prelude::*;
+
+    files
+        .par_iter()
+        .map(|&(key, data)| (String::from(key), read_any(data)))
+        .collect()
+}
+
+/// The length of the ID3v2 tag at the front of `data`, header and footer
+/// included, as declared by its header.
+fn id3v2_declared_len(data: &[u8]) -> Option<usize> {
+    match data {
+        [b'I', b'D', b'3', _major, _minor, flags, size @ ..] if size.len() >= 4 => {
+            let size = size[..4]
+                .iter()
+                .fold(0, |size, &byte| (size << 7) | usize::from(byte & 0x7F));
+            let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+
+            Some(10 + size + footer)
+        }
+        _ => None,
+    }
+}
+
+fn id3v2_len(data: &[u8]) -> usize {
+    id3v2_declared_len(data).map_or(0, |len| len.min(data.len()))
+}
+
+/// The offset of the MPEG audio frames, past any ID3v2 tags at the front of
+/// `data`, and an ID3v1 tag after them. The declared size covers the extended
+/// header, and the footer is added on top, so neither is mistaken for audio.
+fn mp3_audio_offset(data: &[u8]) -> usize {
+    id3v2_tags_len(data) + leading_id3v1(data).map_or(0, <[u8]>::len)
+}
+
+/// The length of the ID3v2 tags at the front of `data`.
+fn id3v2_tags_len(data: &[u8]) -> usize {
+    let mut offset = 0;
+
+    // some taggers prepend a new tag without removing the old one
+    while let Some(len) = id3v2_declared_len(&data[offset..]) {
+        offset = offset.saturating_add(len).min(data.len());
+    }
+
+    offset
+}
+
+/// An ID3v1 tag that a broken tagger wrote to the front of `data`, after any
+/// ID3v2 tags, rather than to the end. `mp3_metadata` would take it for audio,
+/// and lock onto a frame sync somewhere inside it.
+fn leading_id3v1(data: &[u8]) -> Option<&[u8]> {
+    let offset = id3v2_tags_len(data);
+
+    // with no audio after it, it's the one at the end
+    data.get(offset..offset.checked_add(128)?)
+        .filter(|tag| tag.starts_with(b"TAG") && data.len() > offset + 128)
+}
+
+/// Whether `data` starts with an MPEG audio frame header: the frame sync, then
+/// a version, layer, bitrate and sample rate that aren't reserved.
+fn is_mpeg_frame_header(data: &[u8]) -> bool {
+    match data {
+        [0xFF, second, third, ..] if second & 0xE0 == 0xE0 => {
+            second & 0x18 != 0x08
+                && second & 0x06 != 0
+                && third & 0xF0 != 0xF0
+                && third & 0x0C != 0x0C
+        }
+        _ => false,
+    }
+}
+
+/// Writes an arbitrary `key`/`value` text tag into the file, leaving all other
+/// tags and the audio untouched: a `TXXX` frame for MP3, a Vorbis comment for
+/// FLAC, and a freeform `com.apple.iTunes` item for MP4. Ogg files aren't
+/// written to yet, though their Vorbis comments could hold the tag, as that
+/// means splitting the comment packet into pages anew; nor are WAV files.
+pub fn write_tag(data: &[u8], key: &str, value: &str) -> Result<Vec<u8>, FazerError> {
+    use id3::{frame::ExtendedText, TagLike, Version};
+
+    // the built-in readers' detection, so a file is written to in the format
+    // it's read in
+    let format = READERS
+        .iter()
+        .find(|(_, detect, _)| detect(data))
+        .map(|&(format, _, _)| format);
+
+    match format {
+        Some("FLAC") => {
+            // an ID3v2 tag in front of the stream is kept as it is
+            let (prefix, mut frames) = data.split_at(id3v2_len(data));
+            let mut tag = metaflac::Tag::read_from(&mut frames)
+                .map_err(|err| FazerError::Tag(err.to_string()))?;
+
+            tag.vorbis_comments_mut()
+                .set(key.to_ascii_uppercase(), vec![value]);
+
+            let mut out = Vec::with_capacity(data.len());
+            out.extend_from_slice(prefix);
+            tag.write_to(&mut out)
+                .map_err(|err| FazerError::Tag(err.to_string()))?;
+            out.extend_from_slice(frames);
+
+            Ok(out)
+        }
+        Some("MP4") => atoms::set_freeform(data, "com.apple.iTunes", key, value)
+            .ok_or(FazerError::Unsupported("writing to this MP4 layout")),
+        Some("Ogg") => Err(FazerError::Unsupported("writing Ogg tags")),
+        Some("WAV") => Err(FazerError::Unsupported("writing WAV tags")),
+        Some("AMR") => Err(FazerError::Unsupported("writing AMR tags")),
+        // `read_mp3` makes something of any file, so look for a tag or a frame
+        Some("MP3")
+            if id3v2_declared_len(data).is_some()
+                || is_mpeg_frame_header(&data[mp3_audio_offset(data)..]) =>
+        {
+            // a tag that's there but can't be read would lose its frames
+            let tag_len = id3v2_len(data);
+            let mut tag = match id3v2_declared_len(data) {
+                Some(_) => {
+                    id3::Tag::read_from(data).map_err(|err| FazerError::Tag(err.to_string()))?
+                }
+                None => id3::Tag::new(),
+            };
+
+            tag.add_frame(ExtendedText {
+                description: String::from(key),
+                value: String::from(value),
+            });
+
+            let mut out = Vec::with_capacity(data.len());
+            tag.write_to(&mut out, Version::Id3v24)
+                .map_err(|err| FazerError::Tag(err.to_string()))?;
+            out.extend_from_slice(&data[tag_len..]);
+
+            Ok(out)
+        }
+        _ => Err(FazerError::UnrecognizedFormat),
+    }
+}
+
+/// The title, artist and album of the ID3v1 tag at the end of `data`, decoded
+/// as `ReadOptions::id3v1_encoding` describes.
+fn read_id3v1(data: &[u8], encoding: Option<&'static encoding_rs::Encoding>) -> Option<Metadata> {
+    let tag = data.get(data.len().checked_sub(128)?..)?;
+    if !tag.starts_with(b"TAG") {
+        return None;
+    }
+
+    // fixed-width fields, padded with nulls or spaces
+    let text = |range: std::ops::Range<usize>| {
+        let field = tag[range].split(|&byte| byte == 0).next()?;
+        let text = match (encoding, std::str::from_utf8(field)) {
+            (None, Ok(text)) => text.into(),
+            (None, Err(_)) => {
+                encoding_rs::WINDOWS_1252
+                    .decode_without_bom_handling(field)
+                    .0
+            }
+            (Some(encoding), _) => encoding.decode_without_bom_handling(field).0,
+        };
+
+        Some(String::from(text.trim_end())).filter(|text| !text.is_empty())
+    };
+
+    Some(Metadata {
+        title: text(3..33),
+        artist: text(33..63),
+        album: text(63..93),
+        ..Metadata::empty(Format::Mp3)
+    })
+}
+
+/// The length of the ID3v1 tag at the end of `data`, including the enhanced
+/// `TAG+` block in front of it, if there is one.
+fn id3v1_len(data: &[u8]) -> usize {
+    let tag = data.len().checked_sub(128);
+
+    match tag.map(|tag| &data[tag..]) {
+        Some([b'T', b'A', b'G', ..]) => match data.len().checked_sub(128 + 227) {
+            Some(enhanced) if data[enhanced..].starts_with(b"TAG+") => 128 + 227,
+            _ => 128,
+        },
+        _ => 0,
+    }
+}
+
+/// Copies the ID3v1 tag's fields into an ID3v2.4 tag at the front of the file,
+/// without overwriting any frames an existing ID3v2 tag already has, and drops
+/// the ID3v1 tag if `remove_v1` is set. Files without an ID3v1 tag are returned
+/// unchanged.
+pub fn upgrade_id3(data: &[u8], remove_v1: bool) -> Result<Vec<u8>, FazerError> {
+    use id3::{TagLike, Version};
+
+    let v1_len = id3v1_len(data);
+    if v1_len == 0 {
+        return Ok(data.to_vec());
+    }
+
+    let v1 = id3::v1::Tag::read_from(Cursor::new(data))
+        .map_err(|err| FazerError::Tag(err.to_string()))?;
+
+    // the conversion looks the numeric genre up in the ID3v1 genre list, but
+    // writes the year as the ID3v2.3-only `TYER`
+    let mut upgraded = id3::Tag::from(v1);
+    if let Some(year) = upgraded
+        .remove("TYER")
+        .first()
+        .and_then(|frame| frame.content().text())
+    {
+        upgraded.set_text("TDRC", year);
+    }
+
+    let tag_len = id3v2_len(data);
+    let mut tag = id3::Tag::read_from(data).unwrap_or_else(|_| id3::Tag::new());
+
+    for frame in upgraded.frames() {
+        if tag.get(frame.id()).is_none() {
+            tag.add_frame(frame.clone());
+        }
+    }
+
+    let audio = if remove_v1 {
+        &data[tag_len..(data.len() - v1_len).max(tag_len)]
+    } else {
+        &data[tag_len..]
+    };
+
+    let mut out = Vec::with_capacity(data.len());
+    tag.write_to(&mut out, Version::Id3v24)
+        .map_err(|err| FazerError::Tag(err.to_string()))?;
+    out.extend_from_slice(audio);
+
+    Ok(out)
+}
+
+fn flac_cover(tag: &metaflac::Tag) -> Option<&metaflac::block::Picture> {
+    use metaflac::block::PictureType;
+
+    tag.pictures()
+        .find(|picture| picture.picture_type == PictureType::CoverFront)
+        .or_else(|| tag.pictures().next())
+}
+
+/// The APPLICATION block IDs of tools that embedded images before PICTURE
+/// blocks existed: flac-image's `imag`, and FlacFile's `ATCH` attachments.
+const FLAC_IMAGE_APPLICATIONS: [&[u8]; 2] = [b"imag", b"ATCH"];
+
+/// The JPEG and PNG images of a FLAC file's APPLICATION blocks, past any
+/// header the application puts in front of them.
+fn flac_application_images(tag: &metaflac::Tag) -> impl Iterator<Item = &[u8]> {
+    tag.blocks().filter_map(|block| match block {
+        metaflac::Block::Application(application)
+            if FLAC_IMAGE_APPLICATIONS.contains(&&application.id[..]) =>
+        {
+            let data = &application.data[..];
+            let header_len = data.len().min(256);
+
+            let start = (0..header_len).find(|&start| {
+                matches!(
+                    data[start..],
+                    [0xFF, 0xD8, 0xFF, ..] | [0x89, b'P', b'N', b'G', ..]
+                )
+            })?;
+
+            // the magic turns up by chance, so check the image header parses
+            let image = &data[start..];
+            picture::dimensions(image).map(|_| image)
+        }
+        _ => None,
+    })
+}
+
+fn id3_cover(tag: &id3::Tag) -> Option<&id3::frame::Picture> {
+    use id3::frame::PictureType;
+
+    tag.pictures()
+        .find(|picture| picture.picture_type == PictureType::CoverFront)
+        .or_else(|| tag.pictures().next())
+}
+
+fn mp4_meta(ctx: &mp4parse::MediaContext) -> Option<&mp4parse::MetadataBox> {
+    ctx.userdata.as_ref()?.as_ref().ok()?.meta.as_ref()
+}
+
+fn mp4_cover(ctx: &mp4parse::MediaContext) -> Option<&[u8]> {
+    mp4_meta(ctx)?
+        .cover_art
+        .as_ref()?
+        .first()
+        .map(|cover| &cover[..])
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Picture {
+    /// The ID3/FLAC picture type, in snake case (e.g. `cover_front`).
+    pub picture_type: String,
+    pub mime_type: String,
+    pub description: String,
+    #[serde(serialize_with = "serialize_bytes")]
+    pub data: Vec<u8>,
+}
+
+// a `Uint8Array` in JS, rather than an array of numbers
+fn serialize_bytes<S: serde::Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(data)
+}
+
+/// The picture size the wasm exports limit extraction to when none is given,
+/// and past which the metadata readers warn about the cover.
+pub const DEFAULT_MAX_PICTURE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Extracts every embedded picture, in the order they're stored in the file.
+/// Pictures declared larger than `max_bytes` are left out of the result, and
+/// never copied: their FLAC blocks, ID3v2 frames or `covr` data are skipped
+/// before the tag is parsed.
+pub fn read_all_pictures(data: &[u8], max_bytes: usize) -> Vec<Picture> {
+    read_all_pictures_with_warnings(data, max_bytes).0
+}
+
+/// Like `read_all_pictures`, but also returns a warning for each picture that
+/// was left out for its size.
+pub fn read_all_pictures_with_warnings(
+    data: &[u8],
+    max_bytes: usize,
+) -> (Vec<Picture>, Vec<String>) {
+    let mut skipped = Vec::new();
+    let pictures = pictures_up_to(data, max_bytes, &mut skipped);
+
+    let warnings = skipped
+        .into_iter()
+        .map(|len| {
+            format!(
+                "left out a {} byte picture, over the {} byte limit",
+                len, max_bytes
+            )
+        })
+        .collect();
+
+    (pictures, warnings)
+}
+
+/// The pictures of up to `max_bytes`, adding the sizes of the others to
+/// `skipped`.
+fn pictures_up_to(data: &[u8], max_bytes: usize, skipped: &mut Vec<usize>) -> Vec<Picture> {
+    let flac = oversized::flac_blocks(data, max_bytes, skipped);
+    if let Ok(tag) = metaflac::Tag::read_from(&mut flac.as_deref().unwrap_or(data)) {
+        // APPLICATION block images have no picture types or descriptions,
+        // and were only ever used for covers
+        let application_images = flac_application_images(&tag).map(|image| Picture {
+            picture_type: picture::type_name(3).into(),
+            mime_type: picture::mime_type(image).into(),
+            description: String::new(),
+            data: image.to_vec(),
+        });
+
+        return tag
+            .pictures()
+            .map(|picture| Picture {
+                picture_type: picture::type_name(picture.picture_type as u32).into(),
+                mime_type: picture.mime_type.clone(),
+                description: picture.description.clone(),
+                data: picture.data.clone(),
+            })
+            .chain(application_images)
+            .collect();
+    }
+
+    // `mp4parse` would copy out every cover, so they're read from the atoms
+    if let Some(ilst) = atoms::ilst(data) {
+        // `covr` has no picture types or descriptions
+        return atoms::covers(ilst)
+            .filter(|cover| {
+                let fits = cover.len() <= max_bytes;
+                if !fits {
+                    skipped.push(cover.len());
+                }
+                fits
+            })
+            .map(|cover| Picture {
+                picture_type: picture::type_name(3).into(),
+                mime_type: picture::mime_type(cover).into(),
+                description: String::new(),
+                data: cover.to_vec(),
+            })
+            .collect();
+    }
+
+    let id3 = oversized::id3_tag(data, max_bytes, skipped);
+    if let Ok(tag) = id3::Tag::read_from(id3.as_deref().unwrap_or(data)) {
+        return tag
+            .pictures()
+            .map(|picture| Picture {
+                picture_type: picture::type_name(u8::from(picture.picture_type).into()).into(),
+                mime_type: picture.mime_type.clone(),
+                description: picture.description.clone(),
+                data: picture.data.clone(),
+            })
+            .collect();
+    }
+
+    Vec::new()
+}
+
+/// Extracts the embedded front cover, or the first picture if none of them is
+/// marked as the front cover. Only pictures of up to `max_bytes` are considered.
+pub fn read_cover(data: &[u8], max_bytes: usize) -> Option<Vec<u8>> {
+    read_cover_with_warnings(data, max_bytes).0
+}
+
+/// Like `read_cover`, but also returns a warning for each picture that was
+/// left out for its size, as `read_all_pictures_with_warnings` does.
+pub fn read_cover_with_warnings(data: &[u8], max_bytes: usize) -> (Option<Vec<u8>>, Vec<String>) {
+    let (mut pictures, warnings) = read_all_pictures_with_warnings(data, max_bytes);
+
+    let cover = pictures
+        .iter()
+        .position(|picture| picture.picture_type == "cover_front")
+        .unwrap_or(0);
+
+    if cover < pictures.len() {
+        (Some(pictures.swap_remove(cover).data), warnings)
+    } else {
+        (None, warnings)
+    }
+}
+
+/// Extracts the embedded cover and scales it down to fit within `max_dim`
+/// pixels on each side, re-encoded as JPEG.
+#[cfg(feature = "image")]
+pub fn read_cover_thumbnail(data: &[u8], max_dim: u32, max_bytes: usize) -> Option<Vec<u8>> {
+    use image::codecs::jpeg::JpegEncoder;
+
+    let cover = image::load_from_memory(&read_cover(data, max_bytes)?).ok()?;
+
+    let cover = if cover.width() > max_dim || cover.height() > max_dim {
+        cover.thumbnail(max_dim, max_dim)
+    } else {
+        cover
+    };
+
+    let mut thumbnail = Vec::new();
+    JpegEncoder::new_with_quality(&mut thumbnail, 85)
+        .encode_image(&cover.to_rgb8())
+        .ok()?;
+
+    Some(thumbnail)
+}
+
+/// Decodes a WAV or FLAC file and measures the time between the first and the
+/// last sample that is louder than `threshold_db` (in dBFS, e.g. `-60.0`),
+/// leaving out leading and trailing silence. Unlike `seconds`, this reads the
+/// whole stream, so it's much slower.
+#[cfg(feature = "decode")]
+pub fn read_trimmed_duration(data: &[u8], threshold_db: f64) -> Option<f64> {
+    let threshold = 10_f64.powf(threshold_db / 20_f64);
+
+    match data {
+        [b'f', b'L', b'a', b'C', ..] => {
+            let mut reader = claxon::FlacReader::new(data).ok()?;
+            let info = reader.streaminfo();
+            let full_scale = 2_f64.powf(f64::from(info.bits_per_sample) - 1_f64);
+            let samples = reader
+                .samples()
+                .map_while(Result::ok)
+                .map(|sample| f64::from(sample) / full_scale);
+
+            trimmed_duration(samples, info.channels, info.sample_rate, threshold)
+        }
+        [b'R', b'I', b'F', b'F', ..] => {
+            use hound::{SampleFormat, WavReader};
+
+            let mut reader = WavReader::new(Cursor::new(data)).ok()?;
+            let spec = reader.spec();
+            let (channels, sample_rate) = (spec.channels.into(), spec.sample_rate);
+
+            match spec.sample_format {
+                SampleFormat::Float => {
+                    let samples = reader.samples::<f32>().map_while(Result::ok).map(f64::from);
+                    trimmed_duration(samples, channels, sample_rate, threshold)
+                }
+                SampleFormat::Int => {
+                    let full_scale = 2_f64.powf(f64::from(spec.bits_per_sample) - 1_f64);
+                    let samples = reader
+                        .samples::<i32>()
+                        .map_while(Result::ok)
+                        .map(|sample| f64::from(sample) / full_scale);
+
+                    trimmed_duration(samples, channels, sample_rate, threshold)
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The duration between the first and the last frame of interleaved samples,
+/// normalized to `-1.0..=1.0`, in which any channel exceeds `threshold`.
+#[cfg(feature = "decode")]
+fn trimmed_duration(
+    samples: impl Iterator<Item = f64>,
+    channels: u32,
+    sample_rate: u32,
+    threshold: f64,
+) -> Option<f64> {
+    if channels == 0 || sample_rate == 0 {
+        return None;
+    }
+
+    let mut loud = samples
+        .enumerate()
+        .filter(|&(_, sample)| sample.abs() > threshold)
+        .map(|(index, _)| index as u64 / u64::from(channels));
+
+    let (first, last) = match loud.next() {
+        Some(first) => (first, loud.last().unwrap_or(first)),
+        // nothing but silence
+        None => return Some(0_f64),
+    };
+
+    Some((last - first + 1) as f64 / f64::from(sample_rate))
+}
+
+/// Memory-maps the file at `path` and reads its metadata, without copying its
+/// contents onto the heap.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn read_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<Metadata, FazerError> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the mapping is only read from, and is dropped before returning.
+    // Concurrent modification of the file by another process is UB, as with
+    // any read-only mapping.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+
+    read_any(&map).ok_or(FazerError::UnrecognizedFormat)
+}
+
+/// The reads `read_with_timeout` has running, and how many it may have.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+static TIMEOUT_WORKERS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+static MAX_TIMEOUT_WORKERS: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(usize::MAX);
+
+/// Caps the reads `read_with_timeout` runs at once, counting the ones still
+/// running after timing out. Past it, reads fail with `FazerError::Busy`
+/// rather than start another thread. There's no cap until this is called.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn set_max_timeout_workers(max: usize) {
+    MAX_TIMEOUT_WORKERS.store(max, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Counts a `read_with_timeout` thread as running until it's dropped.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+struct TimeoutWorker;
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl TimeoutWorker {
+    fn start() -> Option<Self> {
+        use std::sync::atomic::Ordering;
+
+        let max = MAX_TIMEOUT_WORKERS.load(Ordering::Relaxed);
+        TIMEOUT_WORKERS
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                Some(running + 1).filter(|_| running < max)
+            })
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Drop for TimeoutWorker {
+    fn drop(&mut self) {
+        TIMEOUT_WORKERS.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+    }
+}
+
+/// Like `read_any`, but gives up with `FazerError::Timeout` if reading takes
+/// longer than `timeout`, so that a crafted file can't keep the caller waiting
+/// for long. The read runs on a thread of its own, with its own copy of
+/// `data`, which can't be stopped: after a timeout it carries on in the
+/// background until it's done. So only the wait is bounded, not the CPU time,
+/// memory or threads the reads take up, unless `set_max_timeout_workers` caps
+/// how many run at once.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn read_with_timeout(
+    data: &[u8],
+    timeout: std::time::Duration,
+) -> Result<Option<Metadata>, FazerError> {
+    use std::sync::mpsc::{self, RecvTimeoutError};
+
+    let worker = TimeoutWorker::start().ok_or(FazerError::Busy)?;
+    let data = data.to_vec();
+    let (sender, receiver) = mpsc::channel();
+    let thread = std::thread::Builder::new()
+        .name(String::from("fazer"))
+        .spawn(move || {
+            let _worker = worker;
+            // the receiver is gone if the read timed out
+            let _ = sender.send(read_any(&data));
+        })?;
+
+    match receiver.recv_timeout(timeout) {
+        Ok(metadata) => Ok(metadata),
+        Err(RecvTimeoutError::Timeout) => Err(FazerError::Timeout(timeout)),
+        // the readers' own panics are caught, so this is one outside of them,
+        // which `read_any` would have passed on too
+        Err(RecvTimeoutError::Disconnected) => match thread.join() {
+            Err(payload) => std::panic::resume_unwind(payload),
+            Ok(()) => unreachable!("the thread ended without sending"),
+        },
+    }
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, serde_wasm_bindgen::Error> {
+    // plain objects, rather than `Map`s, so `extra` matches its TS type
+    const SERIALIZER: serde_wasm_bindgen::Serializer =
+        serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+
+    value.serialize(&SERIALIZER)
+}
+
+/// The version of `fazer` itself, e.g. for bug reports about a file it misread.
+pub fn version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+#[wasm_bindgen]
+pub fn fazer(data: Vec<u8>) -> Option<Metadata> {
+    read_any(&data)
+}
+
+/// Like `fazer`, but takes the file as base64, or as a base64 `data:` URL, and
+/// decodes it without going through a JS array first.
+#[wasm_bindgen]
+pub fn fazer_base64(data: &str) -> Result<Option<Metadata>, JsError> {
+    use base64::Engine;
+
+    // a `data:audio/mpeg;base64,` URL's payload follows its first comma
+    let data = match data.strip_prefix("data:") {
+        Some(url) => url.split_once(',').map_or("", |(_, payload)| payload),
+        None => data,
+    };
+    let data = base64::engine::general_purpose::STANDARD.decode(data.trim())?;
+
+    Ok(read_any(&data))
+}
+
+/// Like `fazer`, but warns if the content doesn't match the file's extension.
+#[wasm_bindgen]
+pub fn fazer_with_hint(data: Vec<u8>, extension: &str) -> Option<Metadata> {
+    read_with_hint(&data, extension)
+}
+
+/// Resolves on a later task, so the browser gets to render and handle input.
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| set_timeout(&resolve, 0));
+
+    // `setTimeout` never rejects
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Like `fazer`, but yields to the event loop before each reader so the page
+/// stays responsive. Each reader still runs to completion in one go.
+#[wasm_bindgen]
+pub async fn fazer_async(data: Vec<u8>) -> Option<Metadata> {
+    let mut metadata = None;
+    let mut panics = Vec::new();
+
+    if data.len() >= MIN_HEADER_LEN {
+        for (format, read) in readers(&data) {
+            yield_to_event_loop().await;
+
+            metadata = read_catching_panics(format, read, &data, &mut panics);
+            if metadata.is_some() {
+                break;
+            }
+        }
+    }
+
+    with_panics(metadata, panics)
+}
+
+/// Incrementally reads an MP3 file as it arrives, e.g. from a `ReadableStream`.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Mp3StreamParser {
+    buffer: Vec<u8>,
+    /// Whether `push` already returned the tag's metadata.
+    tag_read: bool,
+}
+
+#[wasm_bindgen]
+impl Mp3StreamParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffers `chunk`, and returns the metadata from the ID3v2 tag once the
+    /// whole tag has arrived, without waiting for the audio. Only the tag's
+    /// fields are filled in, and only the first call after that returns it.
+    pub fn push(&mut self, chunk: &[u8]) -> Option<Metadata> {
+        self.buffer.extend_from_slice(chunk);
+
+        if self.tag_read {
+            return None;
+        }
+
+        // the tag size is in the 10 byte header
+        let tag_len = id3v2_declared_len(&self.buffer)?;
+        let tag = self.buffer.get(..tag_len)?;
+        self.tag_read = true;
+
+        let mut metadata = Metadata::empty(Format::Mp3);
+        match id3::Tag::read_from(tag) {
+            Ok(tag) => set_id3_tags(&mut metadata, &tag, &ReadOptions::default()),
+            Err(err) => metadata.warn(format!("failed to read the ID3v2 tag: {}", err)),
+        }
+
+        Some(metadata)
+    }
+
+    /// Reads the whole file, now that all of it has been pushed.
+    pub fn finish(self) -> Option<Metadata> {
+        read_mp3(&self.buffer)
+    }
+}
+
+#[wasm_bindgen]
+pub fn fazer_raw_pcm(data: Vec<u8>, sample_rate: u32, channels: u32, bit_depth: u16) -> Metadata {
+    read_raw_pcm(&data, sample_rate, channels, bit_depth)
+}
+
+#[wasm_bindgen]
+pub fn fazer_stream_info(data: Vec<u8>) -> Option<Metadata> {
+    read_stream_info(&data)
+}
+
+#[wasm_bindgen]
+pub fn fazer_mp3_seek_table(data: Vec<u8>) -> Result<ISeekTable, JsError> {
+    let table = build_mp3_seek_table(&data);
+
+    Ok(to_js(&table)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_flac_seektable(data: Vec<u8>) -> Result<ISeekTable, JsError> {
+    let table = read_flac_seektable(&data);
+
+    Ok(to_js(&table)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_flac_application(data: Vec<u8>) -> Result<IApplicationBlocks, JsError> {
+    #[derive(Serialize)]
+    struct Block(String, #[serde(serialize_with = "serialize_bytes")] Vec<u8>);
+
+    let blocks = read_flac_application(&data).map(|blocks| {
+        blocks
+            .into_iter()
+            .map(|(id, payload)| Block(id, payload))
+            .collect::<Vec<_>>()
+    });
+
+    Ok(to_js(&blocks)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_flac_padding_bytes(data: Vec<u8>) -> Option<u32> {
+    flac_padding_bytes(&data)
+}
+
+#[wasm_bindgen]
+pub fn fazer_wav_markers(data: Vec<u8>) -> Result<IMarkers, JsError> {
+    let markers = read_wav_markers(&data);
+
+    Ok(to_js(&markers)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_wav_ixml(data: Vec<u8>) -> Option<String> {
+    read_wav_ixml(&data)
+}
+
+#[wasm_bindgen]
+pub fn fazer_wav_bext(data: Vec<u8>) -> Result<IBextInfo, JsError> {
+    let bext = read_wav_bext(&data);
+
+    Ok(to_js(&bext)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_mp4_localized_titles(data: Vec<u8>) -> Result<ILocalizedTitles, JsError> {
+    let titles = read_mp4_localized_titles(&data);
+
+    Ok(to_js(&titles)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_mp4_purchase_info(data: Vec<u8>) -> Result<IPurchaseInfo, JsError> {
+    let info = read_mp4_purchase_info(&data);
+
+    Ok(to_js(&info)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_raw_tags(data: Vec<u8>, normalize_keys: bool) -> Result<IRawTags, JsError> {
+    let tags = read_raw_tags(&data, normalize_keys);
+
+    Ok(to_js(&tags)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_pictures(data: Vec<u8>, max_bytes: Option<usize>) -> Result<IPictures, JsError> {
+    let pictures = read_all_pictures(&data, max_bytes.unwrap_or(DEFAULT_MAX_PICTURE_BYTES));
+
+    Ok(to_js(&pictures)?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_write_tag(data: Vec<u8>, key: &str, value: &str) -> Result<Vec<u8>, JsError> {
+    Ok(write_tag(&data, key, value)?)
+}
+
+#[wasm_bindgen]
+pub fn fazer_upgrade_id3(data: Vec<u8>, remove_v1: bool) -> Result<Vec<u8>, JsError> {
+    Ok(upgrade_id3(&data, remove_v1)?)
+}
+
+#[cfg(feature = "image")]
+#[wasm_bindgen]
+pub fn fazer_cover_thumbnail(
+    data: Vec<u8>,
+    max_dim: u32,
+    max_bytes: Option<usize>,
+) -> Option<Vec<u8>> {
+    read_cover_thumbnail(
+        &data,
+        max_dim,
+        max_bytes.unwrap_or(DEFAULT_MAX_PICTURE_BYTES),
+    )
+}
+
+#[wasm_bindgen]
+pub fn fazer_describe_structure(data: Vec<u8>) -> Option<String> {
+    describe_structure(&data)
+}
+
+#[wasm_bindgen]
+pub fn fazer_version() -> String {
+    String::from(version())
+}
+
+#[wasm_bindgen]
+pub fn fazer_supported_formats() -> Result<IFormats, JsError> {
+    Ok(to_js(&supported_formats())?.unchecked_into())
+}
+
+#[wasm_bindgen]
+pub fn fazer_supported_extensions() -> Result<IExtensions, JsError> {
+    Ok(to_js(&supported_extensions())?.unchecked_into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `fazer` itself can only run on wasm, but is a thin wrapper around `read_any`
+    #[test]
+    fn tiny_inputs() {
+        for len in [0, 1, 10] {
+            assert!(read_any(&vec![0; len]).is_none(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn tiny_inputs_per_format() {
+        let readers: [(&str, ReadFn); 6] = [
+            ("mp3", read_mp3),
+            ("flac", read_flac),
+            ("ogg", read_ogg),
+            ("mp4", read_mp4),
+            ("wav", read_wav),
+            ("amr", read_amr),
+        ];
+
+        for len in [0, 1, 10] {
+            for (format, read) in readers {
+                assert!(read(&vec![0; len]).is_none(), "{}, {} bytes", format, len);
+            }
+        }
+    }
+
+    // so that native callers can read files on several threads at once
+    #[test]
+    fn types_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Metadata>();
+        assert_send_sync::<MetadataBuilder>();
+        assert_send_sync::<Format>();
+        assert_send_sync::<ReadOptions>();
+        assert_send_sync::<DetectResult>();
+        assert_send_sync::<Picture>();
+        assert_send_sync::<PurchaseInfo>();
+        assert_send_sync::<BextInfo>();
+        assert_send_sync::<Mp3StreamParser>();
+        assert_send_sync::<FazerError>();
+    }
+
+    #[test]
+    fn registered_reader_goes_first() {
+        // the registry is shared by the whole test binary, so this only claims
+        // WAV files whose samples end in a marker no other test writes
+        const MARKER: &[u8] = b"registered reader";
+        let mut file = wav(1, 8_000, 8);
+        let marker_at = file.len() - MARKER.len();
+        file[marker_at..].copy_from_slice(MARKER);
+
+        register_reader(
+            "test",
+            |data| data.ends_with(MARKER),
+            |_| Some(Metadata::empty(Format::Amr)),
+        );
+        let format = read_any(&file).map(|metadata| metadata.format());
+        assert!(unregister_reader("test"));
+
+        assert_eq!(format.as_deref(), Some("AMR"));
+        assert_eq!(read_any(&file).unwrap().format(), "WAV");
+        assert!(!unregister_reader("test"));
+    }
+
+    #[test]
+    fn quality_scores() {
+        let flac = Metadata {
+            bit_depth: Some(16),
+            sample_rate: Some(44_100.0),
+            ..Metadata::empty(Format::Flac)
+        };
+        let mp3 = Metadata {
+            bitrate: Some(320.0),
+            sample_rate: Some(44_100.0),
+            ..Metadata::empty(Format::Mp3)
+        };
+
+        assert_eq!(flac.quality_score(), Some(1_016_044));
+        assert_eq!(mp3.quality_score(), Some(3_244));
+        assert_eq!(Metadata::empty(Format::Mp3).quality_score(), None);
+
+        // compressed WAV files are as lossy as the codec they hold
+        let adpcm = Metadata {
+            bit_depth: Some(4),
+            bitrate: Some(352.8),
+            sample_format: Some(String::from("adpcm")),
+            sample_rate: Some(44_100.0),
+            ..Metadata::empty(Format::Wav)
+        };
+        assert_eq!(adpcm.quality_score(), Some(3_572));
+    }
+
+    /// MPEG-1 layer III frames at 128 kbps and 44.1 kHz, without a CRC.
+    fn mp3_frames(count: usize) -> Vec<u8> {
+        let mut frame = vec![0; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        frame.repeat(count)
+    }
+
+    #[test]
+    fn mp3_after_extended_header_and_footer() {
+        // a `PRIV` frame whose payload looks like 32 kbps frame headers
+        let mut priv_frame = b"PRIV\0\0\0\x22\0\0x\0".to_vec();
+        priv_frame.extend([0xFF, 0xFB, 0x10, 0x00].repeat(8));
+
+        // a 6 byte extended header with no flags set
+        let body = [&b"\0\0\0\x06\x01\0"[..], &priv_frame].concat();
+        // the extended header and footer flags, then the syncsafe size
+        let size = [0, 0, 0, body.len() as u8];
+
+        let file = [
+            &b"ID3\x04\0\x50"[..],
+            &size,
+            &body,
+            b"3DI\x04\0\x50",
+            &size,
+            &mp3_frames(10),
+        ]
+        .concat();
+
+        let metadata = read_mp3(&file).unwrap();
+        assert_eq!(metadata.bitrate, Some(128.0));
+        // ten frames of 1152 samples
+        let seconds = metadata.seconds.unwrap();
+        assert!((seconds - 11_520.0 / 44_100.0).abs() < 1e-6, "{}", seconds);
+    }
+
+    #[test]
+    fn merge_fills_missing_fields() {
+        let tags = || Metadata {
+            artist: Some(String::from("Tagged")),
+            seconds: Some(1.0),
+            ..Metadata::empty(Format::Mp3)
+        };
+        let lookup = Metadata {
+            artist: Some(String::from("Looked up")),
+            album: Some(String::from("Album")),
+            seconds: Some(2.0),
+            ..Metadata::empty(Format::Flac)
+        };
+
+        let mut merged = tags();
+        merged.merge(&lookup);
+        assert_eq!(merged.artist.as_deref(), Some("Tagged"));
+        assert_eq!(merged.album.as_deref(), Some("Album"));
+        assert_eq!(merged.seconds, Some(1.0));
+        assert_eq!(merged.format(), "MP3");
+
+        let mut merged = tags();
+        merged.merge_with(&lookup, true);
+        assert_eq!(merged.artist.as_deref(), Some("Looked up"));
+        assert_eq!(merged.album.as_deref(), Some("Album"));
+        assert_eq!(merged.seconds, Some(2.0));
+    }
+
+    #[test]
+    fn mp3_ape_tag_fills_missing_fields() {
+        let item = |key: &str, value: &str| {
+            let mut item = (value.len() as u32).to_le_bytes().to_vec();
+            item.extend([0; 4]);
+            item.extend(key.as_bytes());
+            item.push(0);
+            item.extend(value.as_bytes());
+            item
+        };
+        let items = [item("Artist", "APE Artist"), item("Year", "2004")].concat();
+
+        let mut footer = b"APETAGEX".to_vec();
+        footer.extend(2000_u32.to_le_bytes());
+        footer.extend((items.len() as u32 + 32).to_le_bytes());
+        footer.extend(2_u32.to_le_bytes());
+        footer.extend([0; 12]);
+
+        let mut file = mp3_frames(4);
+        file.extend(items);
+        file.extend(footer);
+        // an ID3v1 tag after it, whose artist goes first
+        file.extend(b"TAG");
+        file.extend([0; 30]);
+        file.extend(b"V1 Artist");
+        file.extend([0; 128 - 42]);
+
+        let metadata = read_mp3(&file).unwrap();
+        assert_eq!(metadata.artist.as_deref(), Some("V1 Artist"));
+        assert_eq!(metadata.date.as_deref(), Some("2004"));
+        assert_eq!(metadata.has_id3v1, Some(true));
+        assert_eq!(metadata.has_id3v2, Some(false));
+
+        let (_, sources) = read_mp3_attributed(&file).unwrap();
+        assert_eq!(sources["artist"], "ID3v1");
+        assert_eq!(sources["date"], "APEv2");
+
+        let options = ReadOptions {
+            read_trailing_tags: false,
+            ..ReadOptions::default()
+        };
+        let metadata = read_mp3_with_options(&file, &options).unwrap();
+        assert_eq!(metadata.artist, None);
+        assert_eq!(metadata.date, None);
+        assert_eq!(metadata.has_id3v1, None);
+    }
+
+    #[test]
+    fn leading_id3v1_tag() {
+        let mut file = b"TAG".to_vec();
+        file.extend(b"Misplaced");
+        file.extend([0; 21]);
+        // an artist that looks like the header of a 32 kbps frame
+        file.extend([0xFF, 0xFB, 0x10, 0x00]);
+        file.extend([0; 128 - 37]);
+        file.extend(mp3_frames(4));
+
+        let metadata = read_mp3(&file).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Misplaced"));
+        assert_eq!(metadata.bitrate, Some(128_f64));
+        assert_eq!(metadata.has_id3v1, Some(false));
+        assert_eq!(
+            metadata.warnings(),
+            ["found an ID3v1 tag at the start of the file"]
+        );
+    }
+
+    #[test]
+    fn id3v1_encodings() {
+        let tag = |title: &[u8]| {
+            let mut file = mp3_frames(4);
+            file.extend(b"TAG");
+            file.extend(title);
+            file.extend(vec![0; 125 - title.len()]);
+            file
+        };
+
+        // Shift-JIS, which isn't valid UTF-8
+        let file = tag(b"\x93\xfa\x96\x7b");
+        let title = |options: &ReadOptions| read_mp3_with_options(&file, options).unwrap().title;
+        assert_eq!(
+            title(&ReadOptions::default()).as_deref(),
+            Some("\u{201c}\u{fa}\u{2013}{")
+        );
+
+        let options = ReadOptions {
+            id3v1_encoding: Some(encoding_rs::SHIFT_JIS),
+            ..ReadOptions::default()
+        };
+        assert_eq!(title(&options).as_deref(), Some("\u{65e5}\u{672c}"));
+
+        // and UTF-8, which is
+        let file = tag("Caf\u{e9}".as_bytes());
+        assert_eq!(read_mp3(&file).unwrap().title.as_deref(), Some("Caf\u{e9}"));
+    }
+
+    #[test]
+    fn podcast_flags() {
+        use id3::{frame::Unknown, Content, Frame, TagLike, Version};
+
+        for (flag, podcast, media_type) in [
+            (1, true, Some("podcast")),
+            // a zero flag still makes a `PCST` frame, but not a podcast
+            (0, false, None),
+        ] {
+            let mut tag = id3::Tag::new();
+            tag.add_frame(Frame::with_content(
+                "PCST",
+                Content::Unknown(Unknown {
+                    data: vec![0, 0, 0, flag],
+                    version: Version::Id3v24,
+                }),
+            ));
+
+            let mut metadata = Metadata::empty(Format::Mp3);
+            set_id3_tags(&mut metadata, &tag, &ReadOptions::default());
+            assert_eq!(metadata.podcast, Some(podcast));
+            assert_eq!(metadata.media_type.as_deref(), media_type);
+        }
+    }
+
+    /// An unrelated `TXXX` or `COMM` frame isn't taken for the source.
+    #[test]
+    fn id3_sources() {
+        use id3::{
+            frame::{Comment, ExtendedText, Unknown},
+            Content, Frame, TagLike, Version,
+        };
+
+        let mut tag = id3::Tag::new();
+        tag.add_frame(ExtendedText {
+            description: String::from("CATALOGNUMBER"),
+            value: String::from("FZR-001"),
+        });
+        tag.add_frame(Comment {
+            lang: String::from("eng"),
+            description: String::from("iTunNORM"),
+            text: String::from(" 00000100 00000100 0 0 0 0 0 0 0 0"),
+        });
+        tag.add_frame(Frame::with_content(
+            "PCST",
+            Content::Unknown(Unknown {
+                data: vec![0, 0, 0, 1],
+                version: Version::Id3v24,
+            }),
+        ));
+
+        let mut file = Vec::new();
+        tag.write_to(&mut file, Version::Id3v24).unwrap();
+        file.extend(mp3_frames(4));
+
+        let (metadata, sources) = read_mp3_attributed(&file).unwrap();
+        assert_eq!(metadata.media_type.as_deref(), Some("podcast"));
+        assert_eq!(sources["media_type"], "ID3v2 PCST");
+        assert_eq!(sources["sound_check"], "ID3v2 COMM:iTunNORM");
+        assert!(!sources.contains_key("comment"));
+    }
+
+    #[test]
+    fn original_date() {
+        use id3::{TagLike, Version};
+
+        let mut tag = id3::Tag::new();
+        tag.set_text("TDOR", "1977-06-10");
+        tag.set_text("TBPM", "120");
+
+        let mut file = Vec::new();
+        tag.write_to(&mut file, Version::Id3v24).unwrap();
+        file.extend(mp3_frames(4));
+
+        // `TDOR` is read into `original_year`, and not into `extra` as well
+        let metadata = read_mp3(&file).unwrap();
+        assert_eq!(metadata.original_year, Some(1977));
+        let extra = metadata.extra().unwrap();
+        assert_eq!(extra.get("bpm").map(String::as_str), Some("120"));
+        assert!(!extra.contains_key("originaldate"));
+    }
+
+    #[test]
+    fn joined_artists() {
+        assert_eq!(
+            join_artists(vec!["Artist", " Artist ", "artist", "Other ", ""]).as_deref(),
+            Some("Artist, Other")
+        );
+        assert_eq!(join_artists(vec![" ", ""]), None);
+    }
+
+    #[test]
+    fn normalized_unicode() {
+        let mut metadata = Metadata::empty(Format::Flac);
+        metadata.artist = Some(String::from("Bjo\u{308}rk"));
+        metadata.set_extra(HashMap::from([(
+            String::from("composer"),
+            String::from("Bjo\u{308}rk"),
+        )]));
+
+        metadata.normalize_unicode();
+        assert_eq!(metadata.artist.as_deref(), Some("Bj\u{f6}rk"));
+        assert_eq!(metadata.extra.unwrap()["composer"], "Bj\u{f6}rk");
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse_position("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_position("03 / 12"), (Some(3), Some(12)));
+        assert_eq!(parse_position("3"), (Some(3), None));
+        assert_eq!(parse_position("/12"), (None, Some(12)));
+        assert_eq!(parse_position("side A"), (None, None));
+
+        assert_eq!(
+            mp4_position(&[0, 0, 0, 3, 0, 12, 0, 0]),
+            (Some(3), Some(12))
+        );
+        assert_eq!(mp4_position(&[0, 0, 0, 3, 0, 0]), (Some(3), None));
+        assert_eq!(mp4_position(b"03/12"), (Some(3), Some(12)));
+    }
+
+    #[test]
+    fn vorbis_track_totals() {
+        let tracks = |comments: &[(&str, &str)]| {
+            let comments = comments
+                .iter()
+                .map(|&(key, value)| (String::from(key), vec![String::from(value)]))
+                .collect();
+
+            let mut metadata = Metadata::empty(Format::Flac);
+            metadata.set_vorbis_comments(&comments);
+            (metadata.track_number, metadata.total_tracks)
+        };
+
+        let conventions: [&[(&str, &str)]; 4] = [
+            &[("TRACKNUMBER", "3/12")],
+            &[("TRACKNUMBER", "3"), ("TRACKTOTAL", "12")],
+            &[("TRACKNUMBER", "03"), ("TOTALTRACKS", "12")],
+            &[
+                ("TRACKNUMBER", "3"),
+                ("TRACKTOTAL", ""),
+                ("TOTALTRACKS", "12"),
+            ],
+        ];
+        for comments in conventions {
+            assert_eq!(tracks(comments), (Some(3), Some(12)));
+        }
+
+        // the total after the number wins
+        assert_eq!(
+            tracks(&[("TRACKNUMBER", "3/12"), ("TRACKTOTAL", "13")]),
+            (Some(3), Some(12))
+        );
+    }
+
+    #[test]
+    fn dates() {
+        for (date, parsed) in [
+            ("2021", "2021"),
+            ("2021-06", "2021-06"),
+            (" 2021-06-15T08:00:00Z", "2021-06-15T08:00:00"),
+            ("2021-06-15T08:00+02:00", "2021-06-15T08:00"),
+            ("June 2021", "June 2021"),
+        ] {
+            assert_eq!(parse_date(date), parsed);
+        }
+    }
+
+    #[test]
+    fn rva2_master_volume() {
+        // a right channel with a 16-bit peak, then the master volume at -3 dB
+        let data = b"track\0\x03\x00\x00\x10\x80\x00\x01\xFA\x00\x00";
+        assert_eq!(parse_rva2(data), Some(-3.0));
+
+        // without a master volume channel
+        assert_eq!(parse_rva2(&data[..11]), None);
+    }
+
+    /// An MP4 atom of `name`, holding `payload`.
+    fn atom(name: &[u8], payload: &[u8]) -> Vec<u8> {
+        [&(payload.len() as u32 + 8).to_be_bytes()[..], name, payload].concat()
+    }
+
+    #[test]
+    fn mp4_localized_titles() {
+        // a QuickTime `©nam` with an English entry (Macintosh code 0) and a
+        // Japanese one (`jpn` packed into 5 bit letters)
+        let jpn = (10 << 10) | (16 << 5) | 14_u16;
+        let entries = [
+            &[0, 5, 0, 0][..],
+            b"Hello",
+            &[0, 6],
+            &jpn.to_be_bytes(),
+            "\u{4eca}\u{65e5}".as_bytes(),
+        ]
+        .concat();
+        let file = atom(b"moov", &atom(b"udta", &atom(b"\xA9nam", &entries)));
+
+        assert_eq!(
+            read_mp4_localized_titles(&file),
+            Some(vec![
+                (String::from("eng"), String::from("Hello")),
+                (String::from("jpn"), String::from("\u{4eca}\u{65e5}")),
+            ])
+        );
+        assert_eq!(read_mp4_localized_titles(&mp3_frames(4)), None);
+    }
+
+    #[test]
+    fn aac_sample_rates() {
+        // AAC LC at 44.1 kHz
+        assert_eq!(aac_sample_rate(&[0x12, 0x10]), Some(44_100));
+        // HE-AAC, explicitly signalled, of a 24 kHz core doubled to 48 kHz
+        assert_eq!(aac_sample_rate(&[0x2B, 0x11, 0x88, 0x00]), Some(24_000));
+        // an escaped object type, and an escaped rate
+        assert_eq!(
+            aac_sample_rate(&[0xF8, 0x1E, 0x00, 0x5D, 0xC0]),
+            Some(12_000)
+        );
+        assert_eq!(aac_sample_rate(&[0x12]), None);
+    }
+
+    #[test]
+    fn mp4_purchase_info() {
+        // an item's `data` atom, with its type and an unset locale
+        let item = |name: &[u8], data_type: u8, value: &[u8]| {
+            atom(
+                name,
+                &atom(
+                    b"data",
+                    &[&[0, 0, 0, data_type, 0, 0, 0, 0][..], value].concat(),
+                ),
+            )
+        };
+
+        let ilst = [
+            item(b"apID", 1, b"buyer@example.com"),
+            item(b"cnID", 21, &1_234_567_u32.to_be_bytes()),
+            item(b"purd", 1, b"2021-06-15 08:00:00"),
+        ]
+        .concat();
+        // ISO `meta` is a full box
+        let meta = atom(
+            b"meta",
+            &[&[0, 0, 0, 0][..], &atom(b"ilst", &ilst)].concat(),
+        );
+        let file = atom(b"moov", &atom(b"udta", &meta));
+
+        assert_eq!(
+            read_mp4_purchase_info(&file),
+            Some(PurchaseInfo {
+                account: Some(String::from("buyer@example.com")),
+                content_id: Some(1_234_567),
+                purchase_date: Some(String::from("2021-06-15 08:00:00")),
+            })
+        );
+        assert_eq!(read_mp4_purchase_info(&mp3_frames(4)), None);
+    }
+
+    #[test]
+    fn picture_limits() {
+        use id3::{
+            frame::{Picture as Frame, PictureType},
+            TagLike, Version,
+        };
+
+        // a picture over the limit between the title and one under it
+        let frame = |picture_type, description: &str, len| Frame {
+            mime_type: String::from("image/png"),
+            picture_type,
+            description: String::from(description),
+            data: vec![0x89; len],
+        };
+        for &version in [Version::Id3v23, Version::Id3v24].iter() {
+            let mut tag = id3::Tag::new();
+            tag.set_title("Title");
+            tag.add_frame(frame(PictureType::CoverFront, "front", 1_000));
+            tag.add_frame(frame(PictureType::CoverBack, "back", 10));
+
+            let mut file = Vec::new();
+            tag.write_to(&mut file, version).unwrap();
+            file.extend(mp3_frames(4));
+
+            let (pictures, warnings) = read_all_pictures_with_warnings(&file, 100);
+            assert_eq!(pictures.len(), 1, "{}", version);
+            assert_eq!(pictures[0].description, "back");
+            assert_eq!(warnings.len(), 1);
+            assert_eq!(read_all_pictures(&file, usize::MAX).len(), 2);
+
+            // with the front cover left out, the back one is all there is
+            let (cover, warnings) = read_cover_with_warnings(&file, 100);
+            assert_eq!(cover, Some(vec![0x89; 10]));
+            assert_eq!(warnings.len(), 1);
+        }
+
+        // and in an MP4 `covr` item, of PNG data
+        let data = |len| {
+            atom(
+                b"data",
+                &[&[0, 0, 0, 14, 0, 0, 0, 0][..], &vec![0x89; len]].concat(),
+            )
+        };
+        let covr = atom(b"covr", &[data(1_000), data(10)].concat());
+        let meta = atom(
+            b"meta",
+            &[&[0, 0, 0, 0][..], &atom(b"ilst", &covr)].concat(),
+        );
+        let file = atom(b"moov", &atom(b"udta", &meta));
+
+        let (pictures, warnings) = read_all_pictures_with_warnings(&file, 100);
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].data.len(), 10);
+        assert_eq!(
+            warnings,
+            ["left out a 1000 byte picture, over the 100 byte limit"]
+        );
+    }
+
+    #[test]
+    fn mp3_fast_duration_from_xing_header() {
+        // without one, the duration is estimated from the file size
+        let mut file = mp3_frames(4);
+        let estimate = read_mp3_fast(&file).unwrap().seconds.unwrap();
+        assert!((estimate - 4.0 * 417.0 * 8.0 / 128_000.0).abs() < 1e-6);
+
+        // a Xing header after the MPEG-1 stereo side information, declaring
+        // a thousand frames
+        file[36..40].copy_from_slice(b"Xing");
+        file[40..44].copy_from_slice(&1_u32.to_be_bytes());
+        file[44..48].copy_from_slice(&1_000_u32.to_be_bytes());
+
+        let seconds = read_mp3_fast(&file).unwrap().seconds.unwrap();
+        assert!(
+            (seconds - 1_152_000.0 / 44_100.0).abs() < 1e-6,
+            "{}",
+            seconds
+        );
+    }
+
+    /// A PCM WAV file holding a second of silence.
+    fn wav(channels: u16, sample_rate: u32, bits_per_sample: u16) -> Vec<u8> {
+        let block_align = channels * bits_per_sample / 8;
+        let data = vec![0; (sample_rate * u32::from(block_align)) as usize];
+
+        let fmt = [
+            &1_u16.to_le_bytes()[..],
+            &channels.to_le_bytes(),
+            &sample_rate.to_le_bytes(),
+            &(sample_rate * u32::from(block_align)).to_le_bytes(),
+            &block_align.to_le_bytes(),
+            &bits_per_sample.to_le_bytes(),
+        ]
+        .concat();
+
+        let chunks = [
+            &b"WAVEfmt "[..],
+            &(fmt.len() as u32).to_le_bytes(),
+            &fmt,
+            b"data",
+            &(data.len() as u32).to_le_bytes(),
+            &data,
+        ]
+        .concat();
+
+        [&b"RIFF"[..], &(chunks.len() as u32).to_le_bytes(), &chunks].concat()
+    }
+
+    #[test]
+    fn wav_bitrate_in_kbps() {
+        let metadata = read_wav(&wav(2, 44_100, 16)).unwrap();
+        assert_eq!(metadata.bitrate, Some(1_411.2));
+
+        let metadata = read_wav(&wav(1, 8_000, 8)).unwrap();
+        assert_eq!(metadata.bitrate, Some(64.0));
+    }
+
+    #[cfg(feature = "decode")]
+    #[test]
+    fn trimmed_duration_skips_silence() {
+        let mut file = wav(1, 8_000, 16);
+        assert_eq!(read_trimmed_duration(&file, -60.0), Some(0.0));
+
+        // half a second at -6 dBFS, after a quarter of a second of silence
+        for sample in 2_000..6_000 {
+            let offset = 44 + sample * 2;
+            file[offset..offset + 2].copy_from_slice(&0x4000_i16.to_le_bytes());
+        }
+
+        assert_eq!(read_trimmed_duration(&file, -60.0), Some(0.5));
+        assert_eq!(read_trimmed_duration(&file, -3.0), Some(0.0));
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn md5_known_answers() {
+        fn md5(chunks: &[&[u8]]) -> String {
+            let mut md5 = md5::Md5::new();
+            for chunk in chunks {
+                md5.update(chunk);
+            }
+            hex(&md5.finish())
+        }
+
+        // the test suite from RFC 1321
+        let digits = b"1234567890".repeat(8);
+        let suite: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (&digits, "57edf4a22be3c955ac49da2e2107b67a"),
+        ];
+        for (input, digest) in suite.iter() {
+            assert_eq!(md5(&[input]), *digest);
+        }
+
+        // 55 bytes leave room for the padding and length in the last block, 56
+        // don't, and 64 fill a block with nothing left over
+        let boundaries = [
+            (55, "ef1772b6dff9a122358552954ad0df65"),
+            (56, "3b0c8ac703f828b04c6c197006d17218"),
+            (63, "b06521f39153d618550606be297466d5"),
+            (64, "014842d480b571495a4a0363793f7367"),
+            (65, "c743a45e0d2e6a95cb859adae0248435"),
+            (128, "e510683b3f5ffe4093d021808bc6ff70"),
+        ];
+        for &(len, digest) in boundaries.iter() {
+            let input = vec![b'a'; len];
+            assert_eq!(md5(&[&input]), digest, "{} bytes", len);
+
+            // the same bytes fed in uneven pieces, some across a block
+            for split in [1, 7, 55, 56, 63].iter().filter(|&&split| split < len) {
+                let (head, tail) = input.split_at(*split);
+                assert_eq!(md5(&[head, tail]), digest, "{} bytes at {}", len, split);
+            }
+            let bytes: Vec<&[u8]> = input.chunks(1).collect();
+            assert_eq!(md5(&bytes), digest, "{} single bytes", len);
+        }
+    }
 }
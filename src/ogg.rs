@@ -0,0 +1,267 @@
+//! A minimal Ogg page reader, for the comment header and granule positions
+//! that `ogg_metadata` doesn't expose.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+fn u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+/// The granule position of pages on which no packet ends.
+pub(crate) const NO_GRANULE_POSITION: u64 = u64::MAX;
+
+pub(crate) struct Page<'a> {
+    pub(crate) granule_position: u64,
+    pub(crate) serial: u32,
+    /// Whether this is the first page of its logical stream.
+    pub(crate) bos: bool,
+    /// The segment table, one lacing value per segment of `body`.
+    lacing: &'a [u8],
+    body: &'a [u8],
+}
+
+pub(crate) struct Pages<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Pages<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+}
+
+impl Page<'_> {
+    /// The size of the whole page, header included.
+    pub(crate) fn len(&self) -> usize {
+        27 + self.lacing.len() + self.body.len()
+    }
+}
+
+impl<'a> Iterator for Pages<'a> {
+    type Item = Page<'a>;
+
+    fn next(&mut self) -> Option<Page<'a>> {
+        let header = self.data.get(self.pos..self.pos.checked_add(27)?)?;
+        if &header[..4] != b"OggS" {
+            return None;
+        }
+
+        let lacing_start = self.pos + 27;
+        let lacing = self
+            .data
+            .get(lacing_start..lacing_start + usize::from(header[26]))?;
+
+        let body_start = lacing_start + lacing.len();
+        let body_len = lacing.iter().map(|&lace| usize::from(lace)).sum::<usize>();
+        let body = self.data.get(body_start..body_start + body_len)?;
+
+        self.pos = body_start + body_len;
+
+        Some(Page {
+            granule_position: u64::from_le_bytes(header[6..14].try_into().ok()?),
+            serial: u32_le(header, 14)?,
+            bos: header[5] & 0x02 != 0,
+            lacing,
+            body,
+        })
+    }
+}
+
+/// A logical stream, as announced by its first page.
+pub(crate) struct Stream<'a> {
+    serial: u32,
+    /// The identification header, which gets a page to itself.
+    head: &'a [u8],
+}
+
+impl<'a> From<Page<'a>> for Stream<'a> {
+    fn from(page: Page<'a>) -> Self {
+        Self {
+            serial: page.serial,
+            head: page.body,
+        }
+    }
+}
+
+/// The links of a chained file, in order, each one the logical streams that
+/// are multiplexed together in it. The first pages of those streams all come
+/// before any others of the link, so a link starts wherever they do.
+pub(crate) fn links(data: &[u8]) -> Vec<Vec<Stream<'_>>> {
+    let mut links: Vec<Vec<Stream<'_>>> = Vec::new();
+    let mut in_headers = false;
+
+    for page in Pages::new(data) {
+        if !page.bos {
+            in_headers = false;
+            continue;
+        }
+
+        match links.last_mut() {
+            Some(link) if in_headers => link.push(page.into()),
+            _ => links.push(vec![page.into()]),
+        }
+        in_headers = true;
+    }
+
+    links
+}
+
+/// The stream of a link that is read: its first Opus or Vorbis stream, so that
+/// e.g. a Skeleton stream in front of it is skipped.
+fn primary<'a, 'b>(link: &'b [Stream<'a>]) -> Option<&'b Stream<'a>> {
+    link.iter().find(|stream| {
+        stream.head.starts_with(b"OpusHead") || stream.head.starts_with(b"\x01vorbis")
+    })
+}
+
+/// The first `count` packets of the first link's primary stream (or failing
+/// that, of the first logical stream), reassembled across page boundaries.
+pub(crate) fn packets(data: &[u8], count: usize) -> Vec<Vec<u8>> {
+    let first_link = Pages::new(data)
+        .take_while(|page| page.bos)
+        .map(Stream::from)
+        .collect::<Vec<_>>();
+
+    let serial = match primary(&first_link).map(|stream| stream.serial) {
+        Some(serial) => serial,
+        None => match Pages::new(data).next() {
+            Some(page) => page.serial,
+            None => return Vec::new(),
+        },
+    };
+
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+
+    for page in Pages::new(data).filter(|page| page.serial == serial) {
+        let mut offset = 0;
+
+        for &lace in page.lacing {
+            let lace = usize::from(lace);
+            packet.extend_from_slice(&page.body[offset..offset + lace]);
+            offset += lace;
+
+            // a lacing value under 255 ends the packet
+            if lace < 255 {
+                packets.push(std::mem::take(&mut packet));
+
+                if packets.len() == count {
+                    return packets;
+                }
+            }
+        }
+    }
+
+    packets
+}
+
+/// Parses an `OpusTags` or Vorbis comment header packet into its comments, in
+/// order and with their field names as written.
+pub(crate) fn raw_comments(packet: &[u8]) -> Option<Vec<(String, String)>> {
+    let data = packet
+        .strip_prefix(b"OpusTags")
+        .or_else(|| packet.strip_prefix(b"\x03vorbis"))?;
+
+    // the vendor string, then the number of comments
+    let mut pos = 4_usize.checked_add(u32_le(data, 0)? as usize)?;
+    let count = u32_le(data, pos)?;
+    pos += 4;
+
+    let mut comments = Vec::new();
+
+    for _ in 0..count {
+        let len = u32_le(data, pos)? as usize;
+        pos += 4;
+
+        let comment = data.get(pos..pos.checked_add(len)?)?;
+        pos += len;
+
+        if let Some((key, value)) = String::from_utf8_lossy(comment).split_once('=') {
+            comments.push((String::from(key), String::from(value)));
+        }
+    }
+
+    Some(comments)
+}
+
+/// Parses an `OpusTags` or Vorbis comment header packet into comments keyed
+/// by their uppercased field names.
+pub(crate) fn comments(packet: &[u8]) -> Option<HashMap<String, Vec<String>>> {
+    let mut comments: HashMap<String, Vec<String>> = HashMap::new();
+
+    for (key, value) in raw_comments(packet)? {
+        comments
+            .entry(key.to_ascii_uppercase())
+            .or_default()
+            .push(value);
+    }
+
+    Some(comments)
+}
+
+/// The granule position of the last page of the stream `serial` that has one.
+fn last_granule_position(data: &[u8], serial: u32) -> Option<u64> {
+    let mut end = data.len();
+
+    while let Some(pos) = data[..end].windows(4).rposition(|window| window == b"OggS") {
+        // the capture pattern can show up inside packets too, so only trust
+        // it if a whole page parses from there
+        if let Some(page) = (Pages { data, pos }).next() {
+            if page.serial == serial && page.granule_position != NO_GRANULE_POSITION {
+                return Some(page.granule_position);
+            }
+        }
+
+        end = pos;
+    }
+
+    None
+}
+
+/// The duration of a stream, from the granule position of its last page. Opus
+/// granules always count 48 kHz samples, including the pre-skip, while Vorbis
+/// ones count samples at the stream's own rate.
+fn stream_duration(data: &[u8], stream: &Stream<'_>) -> Option<f64> {
+    let head = stream.head;
+
+    let (sample_rate, pre_skip) = if head.starts_with(b"OpusHead") {
+        (48_000, u16_le(head, 10)?)
+    } else if head.starts_with(b"\x01vorbis") {
+        (u32_le(head, 12)?, 0)
+    } else {
+        return None;
+    };
+
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let samples = last_granule_position(data, stream.serial)?.saturating_sub(pre_skip.into());
+
+    Some(samples as f64 / f64::from(sample_rate))
+}
+
+/// The duration of the file: of each link's primary stream, summed over the
+/// links of a chained file.
+pub(crate) fn duration(data: &[u8]) -> Option<f64> {
+    let durations = links(data)
+        .iter()
+        .filter_map(|link| stream_duration(data, primary(link)?))
+        .collect::<Vec<_>>();
+
+    if durations.is_empty() {
+        None
+    } else {
+        Some(durations.iter().sum())
+    }
+}
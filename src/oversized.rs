@@ -0,0 +1,191 @@
+//! Leaves the pictures over a size limit out of FLAC metadata blocks and ID3v2
+//! tags before `metaflac` or `id3` parses them, as both copy out every picture
+//! they come across. Each returns `None` when there's nothing to leave out, so
+//! that the file can be parsed as it is.
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::ops::Range;
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn syncsafe(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(
+        bytes
+            .iter()
+            .fold(0, |size, &byte| (size << 7) | u32::from(byte & 0x7F)),
+    )
+}
+
+/// `data` up to `end`, but for the `dropped` ranges, which are in order.
+fn without(data: &[u8], end: usize, dropped: &[Range<usize>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    for range in dropped {
+        out.extend_from_slice(&data[pos..range.start]);
+        pos = range.end;
+    }
+    out.extend_from_slice(&data[pos..end]);
+    out
+}
+
+/// The length of the image in a PICTURE block, as declared after its type,
+/// MIME type, description and dimensions.
+fn flac_picture_len(block: &[u8]) -> Option<usize> {
+    let mime_end = 8_usize.checked_add(u32_be(block, 4)? as usize)?;
+    let description_end = (mime_end + 4).checked_add(u32_be(block, mime_end)? as usize)?;
+
+    Some(u32_be(block, description_end.checked_add(16)?)? as usize)
+}
+
+/// The `fLaC` magic and metadata blocks of the FLAC stream in `data`, without
+/// the PICTURE and image APPLICATION blocks whose images are over `max_bytes`,
+/// the lengths of which are added to `skipped`.
+pub(crate) fn flac_blocks(
+    data: &[u8],
+    max_bytes: usize,
+    skipped: &mut Vec<usize>,
+) -> Option<Vec<u8>> {
+    let start = crate::id3v2_len(data);
+    let data = &data[start..];
+    if !data.starts_with(b"fLaC") {
+        return None;
+    }
+
+    let mut pos = 4;
+    let mut dropped = Vec::new();
+    let mut last_kept = 0;
+
+    loop {
+        let header = data.get(pos..pos + 4)?;
+        let len =
+            usize::from(header[1]) << 16 | usize::from(header[2]) << 8 | usize::from(header[3]);
+        let block = data.get(pos + 4..pos + 4 + len)?;
+
+        let image_len = match header[0] & 0x7F {
+            6 => Some(flac_picture_len(block).unwrap_or(len)),
+            // the image takes up the block after the application's ID, but for
+            // whatever header the application puts in front of it
+            2 if block
+                .get(..4)
+                .is_some_and(|id| crate::FLAC_IMAGE_APPLICATIONS.contains(&id)) =>
+            {
+                Some(len - 4)
+            }
+            _ => None,
+        };
+
+        match image_len.filter(|&image_len| image_len > max_bytes) {
+            Some(image_len) => {
+                skipped.push(image_len);
+                dropped.push(pos..pos + 4 + len);
+            }
+            None => last_kept = pos - dropped.iter().map(Range::len).sum::<usize>(),
+        }
+
+        pos += 4 + len;
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+
+    if dropped.is_empty() {
+        return None;
+    }
+
+    // the last block may have been left out, though STREAMINFO never is
+    let mut blocks = without(data, pos, &dropped);
+    blocks[last_kept] |= 0x80;
+    Some(blocks)
+}
+
+/// Undoes the unsynchronisation that ID3v2.2 and 2.3 apply to the whole tag,
+/// which puts a null after every 0xFF byte.
+fn resynchronise(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, &byte) in data.iter().enumerate() {
+        if !(byte == 0 && i > 0 && data[i - 1] == 0xFF) {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// The ID3v2 tag at the front of `data`, without the `APIC` (or ID3v2.2 `PIC`)
+/// frames whose declared sizes are over `max_bytes`, the sizes of which are
+/// added to `skipped`. A compressed frame is judged by its uncompressed size.
+pub(crate) fn id3_tag(data: &[u8], max_bytes: usize, skipped: &mut Vec<usize>) -> Option<Vec<u8>> {
+    crate::id3v2_declared_len(data)?;
+    let (major, flags) = (data[3], data[5]);
+
+    let body = data.get(10..crate::id3v2_len(data))?;
+    let resynchronised = major < 4 && flags & 0x80 != 0;
+    let body = if resynchronised {
+        Cow::Owned(resynchronise(body))
+    } else {
+        Cow::Borrowed(body)
+    };
+
+    // ID3v2.2 uses the extended header flag for compression, which `id3`
+    // doesn't support anyway
+    let mut pos = match (major, flags & 0x40 != 0) {
+        (_, false) => 0,
+        (2, true) => return None,
+        (3, true) => (u32_be(&body, 0)? as usize).checked_add(4)?,
+        (_, true) => syncsafe(&body, 0)? as usize,
+    };
+    let (id_len, header_len, picture_id): (_, _, &[u8]) = match major {
+        2 => (3, 6, b"PIC"),
+        _ => (4, 10, b"APIC"),
+    };
+
+    let mut dropped = Vec::new();
+
+    // frames run up to the padding, or the end of the tag
+    while let Some(header) = body.get(pos..pos + header_len) {
+        if header[0] == 0 {
+            break;
+        }
+
+        let size = match major {
+            2 => {
+                usize::from(header[3]) << 16 | usize::from(header[4]) << 8 | usize::from(header[5])
+            }
+            3 => u32_be(header, 4)? as usize,
+            _ => syncsafe(header, 4)? as usize,
+        };
+        let end = (pos + header_len).saturating_add(size).min(body.len());
+
+        // the uncompressed size, behind the header of a compressed frame
+        let uncompressed = match major {
+            3 if header[9] & 0x80 != 0 => u32_be(&body, pos + header_len),
+            4 if header[9] & 0x01 != 0 => syncsafe(&body, pos + header_len),
+            _ => None,
+        };
+        let declared = size.max(uncompressed.map_or(0, |len| len as usize));
+
+        if &header[..id_len] == picture_id && declared > max_bytes {
+            skipped.push(declared);
+            dropped.push(pos..end);
+        }
+
+        pos = end;
+    }
+
+    if dropped.is_empty() {
+        return None;
+    }
+
+    // the new tag is neither unsynchronised, nor has a footer
+    let frames = without(&body, pos, &dropped);
+    let len = frames.len() as u32;
+    let size = [len >> 21, len >> 14, len >> 7, len].map(|bits| (bits & 0x7F) as u8);
+    let flags = if resynchronised { flags & !0x80 } else { flags } & !0x10;
+
+    Some([&data[..5], &[flags], &size, &frames].concat())
+}
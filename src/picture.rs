@@ -0,0 +1,95 @@
+//! Just enough image header parsing to report embedded picture dimensions,
+//! without decoding the image.
+
+use std::convert::TryInto;
+
+fn u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+
+        match *data.get(pos + 1)? {
+            // fill bytes
+            0xFF => pos += 1,
+            // markers without a payload
+            0x01 | 0xD0..=0xD8 => pos += 2,
+            // start of frame, other than DHT, JPG and DAC which share the range
+            marker @ 0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = u16_be(data, pos + 5)?;
+                let width = u16_be(data, pos + 7)?;
+                return Some((width.into(), height.into()));
+            }
+            _ => pos += 2 + usize::from(u16_be(data, pos + 2)?),
+        }
+    }
+}
+
+/// The `(width, height)` of a JPEG, PNG or GIF image, read from its header.
+pub(crate) fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data {
+        [0xFF, 0xD8, ..] => jpeg_dimensions(data),
+        [0x89, b'P', b'N', b'G', ..] if data.get(12..16)? == b"IHDR" => {
+            Some((u32_be(data, 16)?, u32_be(data, 20)?))
+        }
+        [b'G', b'I', b'F', ..] => {
+            let width = u16::from_le_bytes(data.get(6..8)?.try_into().ok()?);
+            let height = u16::from_le_bytes(data.get(8..10)?.try_into().ok()?);
+            Some((width.into(), height.into()))
+        }
+        _ => None,
+    }
+}
+
+/// The name of an ID3/FLAC picture type code, which both formats share.
+pub(crate) fn type_name(code: u32) -> &'static str {
+    match code {
+        1 => "icon",
+        2 => "other_icon",
+        3 => "cover_front",
+        4 => "cover_back",
+        5 => "leaflet",
+        6 => "media",
+        7 => "lead_artist",
+        8 => "artist",
+        9 => "conductor",
+        10 => "band",
+        11 => "composer",
+        12 => "lyricist",
+        13 => "recording_location",
+        14 => "during_recording",
+        15 => "during_performance",
+        16 => "screen_capture",
+        17 => "bright_fish",
+        18 => "illustration",
+        19 => "band_logo",
+        20 => "publisher_logo",
+        _ => "other",
+    }
+}
+
+/// Sniffs the MIME type of an image from its magic bytes, for containers like
+/// MP4 that don't store one.
+pub(crate) fn mime_type(data: &[u8]) -> &'static str {
+    match data {
+        [0xFF, 0xD8, ..] => "image/jpeg",
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [b'G', b'I', b'F', ..] => "image/gif",
+        [b'B', b'M', ..] => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,193 @@
+//! A minimal RIFF chunk walker, for the parts of WAV files that `hound` doesn't
+//! expose.
+
+use std::convert::TryInto;
+
+pub(crate) const WAVE_FORMAT_PCM: u16 = 0x0001;
+pub(crate) const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+pub(crate) const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Names the sample encoding of a format tag, for the compressed codecs that
+/// turn up in WAV files.
+pub(crate) fn codec_name(format_tag: u16) -> &'static str {
+    match format_tag {
+        0x0002 | 0x0011 => "adpcm",
+        0x0006 => "alaw",
+        0x0007 => "mulaw",
+        0x0031 => "gsm",
+        0x0050 | 0x0055 => "mpeg",
+        _ => "compressed",
+    }
+}
+
+fn u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+pub(crate) struct Chunk<'a> {
+    pub(crate) id: [u8; 4],
+    pub(crate) data: &'a [u8],
+    /// Whether the chunk's declared size runs past the end of the buffer.
+    pub(crate) truncated: bool,
+}
+
+pub(crate) struct Chunks<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Chunks<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Option<Self> {
+        if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WAVE" {
+            return None;
+        }
+
+        Some(Self { data, pos: 12 })
+    }
+
+    /// Walks the sub-chunks of a `LIST` chunk of the given list type.
+    pub(crate) fn list(data: &'a [u8], list_type: &[u8; 4]) -> Option<Self> {
+        if data.get(..4)? != list_type {
+            return None;
+        }
+
+        Some(Self { data, pos: 4 })
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Chunk<'a>> {
+        let header = self.data.get(self.pos..self.pos.checked_add(8)?)?;
+        let id = header[..4].try_into().ok()?;
+        let size = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+
+        let start = self.pos + 8;
+        // chunks that run past the end of the buffer are clipped, rather than dropped
+        let end = start.saturating_add(size).min(self.data.len());
+        // chunks are padded to an even size
+        self.pos = end.saturating_add(size & 1);
+
+        Some(Chunk {
+            id,
+            data: &self.data[start..end],
+            truncated: end - start < size,
+        })
+    }
+}
+
+pub(crate) struct Extensible {
+    pub(crate) valid_bits_per_sample: u16,
+    pub(crate) channel_mask: u32,
+    pub(crate) sub_format: u16,
+}
+
+pub(crate) struct Fmt {
+    pub(crate) format_tag: u16,
+    pub(crate) channels: u16,
+    pub(crate) sample_rate: u32,
+    pub(crate) byte_rate: u32,
+    pub(crate) bits_per_sample: u16,
+    pub(crate) extensible: Option<Extensible>,
+}
+
+impl Fmt {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let format_tag = u16_le(data, 0)?;
+
+        let extensible = if format_tag == WAVE_FORMAT_EXTENSIBLE {
+            Some(Extensible {
+                valid_bits_per_sample: u16_le(data, 18)?,
+                channel_mask: u32_le(data, 20)?,
+                // the first two bytes of the sub-format GUID are the format tag
+                sub_format: u16_le(data, 24)?,
+            })
+        } else {
+            None
+        };
+
+        Some(Self {
+            format_tag,
+            channels: u16_le(data, 2)?,
+            sample_rate: u32_le(data, 4)?,
+            byte_rate: u32_le(data, 8)?,
+            bits_per_sample: u16_le(data, 14)?,
+            extensible,
+        })
+    }
+
+    /// The format tag, with `WAVE_FORMAT_EXTENSIBLE` resolved to its sub-format.
+    pub(crate) fn effective_format_tag(&self) -> u16 {
+        self.extensible
+            .as_ref()
+            .map_or(self.format_tag, |extensible| extensible.sub_format)
+    }
+}
+
+pub(crate) fn read_fmt(data: &[u8]) -> Option<Fmt> {
+    Chunks::new(data)?
+        .find(|chunk| &chunk.id == b"fmt ")
+        .and_then(|chunk| Fmt::parse(chunk.data))
+}
+
+/// Decodes a null-terminated `INFO` text value.
+pub(crate) fn info_text(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_end_matches('\0').trim();
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(String::from(text))
+    }
+}
+
+/// Speaker names, indexed by their bit in an extensible channel mask.
+const SPEAKERS: [&str; 18] = [
+    "FL", "FR", "FC", "LFE", "BL", "BR", "FLC", "FRC", "BC", "SL", "SR", "TC", "TFL", "TFC", "TFR",
+    "TBL", "TBC", "TBR",
+];
+
+/// Names a channel mask after the common layout it matches, or lists its
+/// speakers (e.g. `FL+FR+LFE+BC`) if it doesn't match any.
+pub(crate) fn channel_layout(mask: u32) -> Option<String> {
+    let name = match mask {
+        // no speaker assignment
+        0 => return None,
+        0x4 => "mono",
+        0x3 => "stereo",
+        0xB => "2.1",
+        0x7 => "3.0",
+        0x33 => "quad",
+        0x603 => "quad(side)",
+        0x107 => "4.0",
+        0x37 => "5.0",
+        0x607 => "5.0(side)",
+        0x3F => "5.1",
+        0x60F => "5.1(side)",
+        0x70F => "6.1",
+        0x63F => "7.1",
+        0xFF => "7.1(wide)",
+        _ => {
+            let speakers = SPEAKERS
+                .iter()
+                .enumerate()
+                .filter(|&(bit, _)| mask & (1 << bit) != 0)
+                .map(|(_, &speaker)| speaker)
+                .collect::<Vec<_>>();
+
+            return Some(speakers.join("+"));
+        }
+    };
+
+    Some(String::from(name))
+}
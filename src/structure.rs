@@ -0,0 +1,183 @@
+//! A plain text outline of a file's container, for `describe_structure`.
+
+use crate::{atoms, ogg, riff};
+use std::fmt::Write;
+
+/// The MP4 boxes that hold nothing but other boxes.
+const MP4_CONTAINERS: [&[u8; 4]; 13] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"edts", b"dinf", b"mvex", b"moof",
+    b"traf", b"mfra", b"ilst",
+];
+
+/// How deep to follow nested boxes, so that a file of boxes inside boxes can't
+/// overflow the stack.
+const MAX_DEPTH: usize = 16;
+
+fn line(out: &mut String, depth: usize, name: &str, size: usize, truncated: bool) {
+    let _ = write!(
+        out,
+        "{:indent$}{} ({} bytes",
+        "",
+        name,
+        size,
+        indent = 2 * depth
+    );
+    if truncated {
+        out.push_str(", truncated");
+    }
+    out.push_str(")\n");
+}
+
+/// A four character code as it's usually written, with the bytes of e.g.
+/// `©nam` taken as Latin-1.
+fn fourcc(id: &[u8; 4]) -> String {
+    id.iter().map(|&byte| char::from(byte)).collect()
+}
+
+pub(crate) fn mp4(data: &[u8]) -> String {
+    let mut out = String::new();
+    mp4_boxes(&mut out, data, 0, false);
+    out
+}
+
+fn mp4_boxes(out: &mut String, data: &[u8], depth: usize, in_ilst: bool) {
+    for atom in atoms::Atoms::new(data) {
+        line(
+            out,
+            depth,
+            &fourcc(&atom.name),
+            atom.range.len(),
+            atom.truncated,
+        );
+
+        if depth + 1 >= MAX_DEPTH {
+            continue;
+        }
+
+        // the items of `ilst` hold their `data` boxes, and `meta` is a full box
+        // in the ISO flavour, but not in QuickTime's
+        if MP4_CONTAINERS.contains(&&atom.name) || in_ilst {
+            mp4_boxes(out, atom.data, depth + 1, &atom.name == b"ilst");
+        } else if &atom.name == b"meta" {
+            let children = match atom.data.get(4..8) {
+                Some(b"hdlr") => atom.data,
+                _ => atom.data.get(4..).unwrap_or_default(),
+            };
+            mp4_boxes(out, children, depth + 1, false);
+        }
+    }
+}
+
+pub(crate) fn riff(data: &[u8]) -> Option<String> {
+    let chunks = riff::Chunks::new(data)?;
+
+    let mut out = String::new();
+    line(&mut out, 0, "RIFF WAVE", data.len(), false);
+    riff_chunks(&mut out, chunks, 1);
+    Some(out)
+}
+
+fn riff_chunks(out: &mut String, chunks: riff::Chunks<'_>, depth: usize) {
+    for chunk in chunks {
+        let list_type = match (&chunk.id, chunk.data.get(..4)) {
+            (b"LIST", Some(list_type)) => {
+                let mut id = [0; 4];
+                id.copy_from_slice(list_type);
+                Some(id)
+            }
+            _ => None,
+        };
+
+        match list_type {
+            Some(list_type) => {
+                let name = format!("LIST {}", fourcc(&list_type));
+                line(out, depth, &name, chunk.data.len() + 8, chunk.truncated);
+
+                if let Some(children) = riff::Chunks::list(chunk.data, &list_type) {
+                    riff_chunks(out, children, depth + 1);
+                }
+            }
+            None => line(
+                out,
+                depth,
+                &fourcc(&chunk.id),
+                chunk.data.len() + 8,
+                chunk.truncated,
+            ),
+        }
+    }
+}
+
+fn flac_block_name(block_type: u8) -> &'static str {
+    match block_type {
+        0 => "STREAMINFO",
+        1 => "PADDING",
+        2 => "APPLICATION",
+        3 => "SEEKTABLE",
+        4 => "VORBIS_COMMENT",
+        5 => "CUESHEET",
+        6 => "PICTURE",
+        127 => "invalid",
+        _ => "reserved",
+    }
+}
+
+/// Outlines the metadata blocks of the FLAC stream that starts `offset` bytes
+/// into `data`, after any ID3v2 tag.
+pub(crate) fn flac(data: &[u8], offset: usize) -> String {
+    let mut out = String::new();
+    if offset > 0 {
+        line(&mut out, 0, "ID3v2", offset, false);
+    }
+    line(&mut out, 0, "fLaC", 4, false);
+
+    let mut pos = offset + 4;
+    while let Some(header) = data.get(pos..pos + 4) {
+        let size =
+            usize::from(header[1]) << 16 | usize::from(header[2]) << 8 | usize::from(header[3]);
+        let available = (data.len() - pos).min(size + 4);
+        line(
+            &mut out,
+            1,
+            flac_block_name(header[0] & 0x7F),
+            available,
+            available < size + 4,
+        );
+
+        pos += available;
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+
+    if pos < data.len() {
+        line(&mut out, 0, "audio frames", data.len() - pos, false);
+    }
+
+    out
+}
+
+pub(crate) fn ogg(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut len = 0;
+
+    for page in ogg::Pages::new(data) {
+        let mut name = format!("OggS stream {:08X}", page.serial);
+        if page.granule_position != ogg::NO_GRANULE_POSITION {
+            let _ = write!(name, ", granule {}", page.granule_position);
+        }
+        if page.bos {
+            name.push_str(", first page");
+        }
+
+        line(&mut out, 0, &name, page.len(), false);
+        len += page.len();
+    }
+
+    // the pages stop at the first one that's cut off or corrupt
+    if len < data.len() {
+        line(&mut out, 0, "unreadable", data.len() - len, false);
+    }
+
+    out
+}
@@ -0,0 +1,244 @@
+//! A minimal DTS and AC-3 sync frame reader, for the WAV files that carry
+//! surround audio in place of PCM samples, either as a bare stream or in the
+//! IEC 61937 bursts S/PDIF sends it in.
+
+pub(crate) enum Codec {
+    Dts,
+    Ac3,
+}
+
+pub(crate) struct Stream {
+    pub(crate) codec: Codec,
+    pub(crate) channels: u32,
+    pub(crate) sample_rate: u32,
+    /// In kbps. DTS streams of an open or variable rate don't declare one.
+    pub(crate) bitrate: Option<f64>,
+}
+
+/// How far into the audio to look for a sync frame.
+const SCAN_LEN: usize = 64 * 1024;
+
+/// Enough of a frame to cover the fields read from its header.
+const HEADER_LEN: usize = 16;
+
+const DTS_SAMPLE_RATES: [u32; 16] = [
+    0, 8_000, 16_000, 32_000, 0, 0, 11_025, 22_050, 44_100, 0, 0, 12_000, 24_000, 48_000, 0, 0,
+];
+
+/// The channels of each DTS channel arrangement, not counting the LFE one.
+const DTS_CHANNELS: [u32; 16] = [1, 2, 2, 2, 2, 3, 3, 4, 4, 5, 6, 6, 6, 7, 8, 8];
+
+const DTS_BITRATES: [f64; 29] = [
+    32.0, 56.0, 64.0, 96.0, 112.0, 128.0, 192.0, 224.0, 256.0, 320.0, 384.0, 448.0, 512.0, 576.0,
+    640.0, 768.0, 960.0, 1_024.0, 1_152.0, 1_280.0, 1_344.0, 1_408.0, 1_411.2, 1_472.0, 1_536.0,
+    1_920.0, 2_048.0, 3_072.0, 3_840.0,
+];
+
+const AC3_BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+/// The channels of each AC-3 audio coding mode, not counting the LFE one.
+const AC3_CHANNELS: [u32; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+
+/// Finds the first DTS or AC-3 frame near the start of `audio`, in any of the
+/// byte orders and packings WAV files use.
+pub(crate) fn find(audio: &[u8]) -> Option<Stream> {
+    let audio = &audio[..audio.len().min(SCAN_LEN)];
+
+    (0..audio.len())
+        .step_by(2)
+        .find_map(|offset| frame(&audio[offset..]))
+}
+
+fn frame(data: &[u8]) -> Option<Stream> {
+    match data {
+        // an IEC 61937 burst preamble, in little-endian words like the rest
+        [0x72, 0xF8, 0x1F, 0x4E, data_type, ..] => {
+            let payload = swapped(data.get(8..)?);
+            match data_type & 0x1F {
+                1 => ac3(&payload),
+                11..=13 => dts(&payload),
+                _ => None,
+            }
+        }
+        // a sync word turns up in PCM by chance, so make sure another frame
+        // follows. The 14 bit one is just the stereo sample (8191, -6144)
+        [0x7F, 0xFE, 0x80, 0x01, ..] => bare_dts(data, DtsPacking::Words),
+        [0xFE, 0x7F, 0x01, 0x80, ..] => bare_dts(data, DtsPacking::SwappedWords),
+        // the 14 bit packing of DTS CDs, which keeps samples from clipping
+        [0x1F, 0xFF, 0xE8, 0x00, ..] => bare_dts(data, DtsPacking::Packed),
+        [0xFF, 0x1F, 0x00, 0xE8, ..] => bare_dts(data, DtsPacking::SwappedPacked),
+        [0x0B, 0x77, ..] => bare_ac3(data, [0x0B, 0x77]),
+        [0x77, 0x0B, ..] => bare_ac3(data, [0x77, 0x0B]),
+        _ => None,
+    }
+}
+
+/// The header of the frame at the start of `data`, with each pair of bytes
+/// swapped.
+fn swapped(data: &[u8]) -> Vec<u8> {
+    data[..data.len().min(2 * HEADER_LEN)]
+        .chunks_exact(2)
+        .flat_map(|pair| [pair[1], pair[0]])
+        .collect()
+}
+
+/// The header of the frame at the start of `data`, from the low 14 bits of
+/// each big-endian word.
+fn unpacked(data: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    let (mut value, mut len) = (0_u32, 0);
+
+    for word in data.chunks_exact(2) {
+        if header.len() >= HEADER_LEN {
+            break;
+        }
+
+        value = value << 14 | u32::from(u16::from_be_bytes([word[0], word[1]]) & 0x3FFF);
+        len += 14;
+
+        while len >= 8 {
+            len -= 8;
+            header.push((value >> len) as u8);
+        }
+        value &= (1 << len) - 1;
+    }
+
+    header
+}
+
+/// The `len` bits of `data` starting at bit `start`, most significant first.
+fn bits(data: &[u8], start: usize, len: usize) -> Option<u32> {
+    (start..start + len).try_fold(0, |value, bit| {
+        let byte = data.get(bit / 8)?;
+        Some(value << 1 | u32::from(byte >> (7 - bit % 8) & 1))
+    })
+}
+
+/// Reads a DTS core frame header, in big-endian words.
+fn dts(header: &[u8]) -> Option<Stream> {
+    let sample_rate = DTS_SAMPLE_RATES[bits(header, 66, 4)? as usize];
+    if sample_rate == 0 {
+        return None;
+    }
+
+    // arrangements past the table are user defined
+    let channels = *DTS_CHANNELS.get(bits(header, 60, 6)? as usize)?;
+    let lfe = matches!(bits(header, 85, 2)?, 1 | 2);
+
+    Some(Stream {
+        codec: Codec::Dts,
+        channels: channels + u32::from(lfe),
+        sample_rate,
+        bitrate: DTS_BITRATES.get(bits(header, 70, 5)? as usize).copied(),
+    })
+}
+
+/// The byte orders and packings of a bare DTS stream.
+enum DtsPacking {
+    Words,
+    SwappedWords,
+    Packed,
+    SwappedPacked,
+}
+
+/// The length in bytes of the DTS core frame with the given header, in
+/// big-endian words.
+fn dts_frame_len(header: &[u8]) -> Option<usize> {
+    // frames shorter than this are invalid
+    match bits(header, 46, 14)? {
+        size @ 95.. => Some(size as usize + 1),
+        _ => None,
+    }
+}
+
+/// Reads a bare DTS stream, in the given byte order and packing.
+fn bare_dts(data: &[u8], packing: DtsPacking) -> Option<Stream> {
+    let header = match packing {
+        DtsPacking::Words => data[..data.len().min(HEADER_LEN)].to_vec(),
+        DtsPacking::SwappedWords => swapped(data),
+        DtsPacking::Packed => unpacked(data),
+        DtsPacking::SwappedPacked => unpacked(&swapped(data)),
+    };
+
+    // 14 bit words take up 16 bits each
+    let len = match packing {
+        DtsPacking::Words | DtsPacking::SwappedWords => dts_frame_len(&header)?,
+        DtsPacking::Packed | DtsPacking::SwappedPacked => dts_frame_len(&header)? * 8 / 7,
+    };
+    if data.get(len..len + 4)? != &data[..4] {
+        return None;
+    }
+
+    dts(&header)
+}
+
+/// Reads an AC-3 sync frame header, in big-endian words.
+fn ac3(header: &[u8]) -> Option<Stream> {
+    let sample_rate = match bits(header, 32, 2)? {
+        0 => 48_000,
+        1 => 44_100,
+        2 => 32_000,
+        _ => return None,
+    };
+    let bitrate = *AC3_BITRATES.get(bits(header, 34, 6)? as usize / 2)?;
+
+    // E-AC-3 and its successors count up from 11
+    if bits(header, 40, 5)? > 10 {
+        return None;
+    }
+
+    // the mix levels and Dolby Surround mode sit between the coding mode and
+    // the LFE flag, depending on the mode
+    let mode = bits(header, 48, 3)?;
+    let mut lfe = 51;
+    if mode & 1 != 0 && mode != 1 {
+        lfe += 2;
+    }
+    if mode & 4 != 0 {
+        lfe += 2;
+    }
+    if mode == 2 {
+        lfe += 2;
+    }
+
+    Some(Stream {
+        codec: Codec::Ac3,
+        channels: AC3_CHANNELS[mode as usize] + bits(header, lfe, 1)?,
+        sample_rate,
+        bitrate: Some(f64::from(bitrate)),
+    })
+}
+
+/// The length in bytes of the AC-3 sync frame with the given header.
+fn ac3_frame_len(header: &[u8]) -> Option<usize> {
+    let code = bits(header, 34, 6)?;
+    let bitrate = *AC3_BITRATES.get(code as usize / 2)?;
+
+    // in 16-bit words, with 44.1 kHz frames alternating in length to even out
+    let words = match bits(header, 32, 2)? {
+        0 => bitrate * 2,
+        1 => bitrate * 96_000 / 44_100 + (code & 1),
+        2 => bitrate * 3,
+        _ => return None,
+    };
+
+    Some(words as usize * 2)
+}
+
+/// Reads a bare AC-3 stream, whose sync word is `sync` in its byte order.
+fn bare_ac3(data: &[u8], sync: [u8; 2]) -> Option<Stream> {
+    let header = if sync[0] == 0x0B {
+        data[..data.len().min(HEADER_LEN)].to_vec()
+    } else {
+        swapped(data)
+    };
+
+    let len = ac3_frame_len(&header)?;
+    if data.get(len..len + 2)? != sync {
+        return None;
+    }
+
+    ac3(&header)
+}
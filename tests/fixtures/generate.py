@@ -0,0 +1,506 @@
+#!/usr/bin/env python3
+"""Builds the fixtures in this directory.
+
+Each one is a real, well-formed file of its format, a fraction of a second to
+a few seconds of silence long, written out by hand so it stays a few KiB and
+its every field is known. Run it from anywhere to regenerate them.
+"""
+
+import hashlib
+import os
+import struct
+
+OUT = os.path.dirname(os.path.abspath(__file__))
+
+
+def write(name, data):
+    with open(os.path.join(OUT, name), 'wb') as f:
+        f.write(data)
+
+
+# ID3v2
+
+def syncsafe(n):
+    return bytes([(n >> 21) & 0x7F, (n >> 14) & 0x7F, (n >> 7) & 0x7F, n & 0x7F])
+
+
+def id3v23(frames):
+    body = b''
+    for frame_id, text in frames:
+        if isinstance(text, bytes):
+            data = text
+        else:
+            # UTF-8 isn't an ID3v2.3 encoding, so stick to Latin-1
+            data = b'\x00' + text.encode('latin-1')
+        body += frame_id + struct.pack('>IH', len(data), 0) + data
+    return b'ID3\x03\x00\x00' + syncsafe(len(body)) + body
+
+
+TAGS = [
+    (b'TIT2', 'Fixture Title'),
+    (b'TPE1', 'Fixture Artist'),
+    (b'TALB', 'Fixture Album'),
+    (b'TCON', 'Ambient'),
+    (b'TRCK', '03/12'),
+]
+
+
+# MP3
+
+BITRATES = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320]
+
+
+def mp3_frame(kbps, payload=b''):
+    """An MPEG-1 layer III frame at 44.1 kHz, joint stereo, without CRC."""
+    header = 0xFFFB0000 | (BITRATES.index(kbps) << 12) | (1 << 6)
+    size = 144 * kbps * 1000 // 44100
+    return struct.pack('>I', header) + payload + b'\x00' * (size - 4 - len(payload))
+
+
+def xing(frame_count, tag=b'Xing'):
+    # after 32 bytes of MPEG-1 stereo side information; only the frame count
+    return b'\x00' * 32 + tag + struct.pack('>II', 1, frame_count)
+
+
+def mp3():
+    write('cbr.mp3', id3v23(TAGS) + mp3_frame(128) * 40)
+
+    bitrates = [128, 160, 192, 256] * 10
+    frames = b''.join(mp3_frame(kbps) for kbps in bitrates)
+    # a `TXXX` frame's description and value are null-separated
+    tags = TAGS + [(b'TXXX', 'iTunPGAP\x000'), (b'TXXX', 'ITUNESMEDIATYPE\x00Audiobook')]
+    # both channels up by half of the 16-bit full scale
+    tags += [(b'RVAD', struct.pack('>BBHH', 0b11, 16, 0x8000, 0x8000))]
+    write('vbr.mp3', id3v23(tags) + mp3_frame(128, xing(len(bitrates))) + frames)
+
+
+# FLAC
+
+def crc8(data):
+    crc = 0
+    for byte in data:
+        crc ^= byte
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x07) & 0xFF if crc & 0x80 else (crc << 1) & 0xFF
+    return crc
+
+
+def crc16(data):
+    crc = 0
+    for byte in data:
+        crc ^= byte << 8
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x8005) & 0xFFFF if crc & 0x8000 else (crc << 1) & 0xFFFF
+    return crc
+
+
+FLAC_RATES = {44100: 9, 48000: 10, 96000: 11}
+FLAC_DEPTHS = {16: 4, 24: 6}
+
+
+def flac_frame(number, block_size, sample_rate, bits):
+    """A stereo frame of two CONSTANT silent subframes, of a 4096 sample block."""
+    assert block_size == 4096 and number < 128
+    header = bytes([
+        0xFF, 0xF8,
+        (12 << 4) | FLAC_RATES[sample_rate],
+        (1 << 4) | (FLAC_DEPTHS[bits] << 1),
+        number,
+    ])
+    header += bytes([crc8(header)])
+    subframe = b'\x00' + b'\x00' * (bits // 8)
+    frame = header + subframe * 2
+    return frame + struct.pack('>H', crc16(frame))
+
+
+def flac_block(block_type, data, last=False):
+    return bytes([(0x80 if last else 0) | block_type]) + struct.pack('>I', len(data))[1:] + data
+
+
+def streaminfo(sample_rate, bits, total_samples, frame_size, md5):
+    packed = (sample_rate << 44) | (1 << 41) | ((bits - 1) << 36) | total_samples
+    return (struct.pack('>HH', 4096, 4096) + struct.pack('>I', frame_size)[1:] * 2
+            + struct.pack('>Q', packed) + md5)
+
+
+def vorbis_comment(vendor, comments):
+    data = struct.pack('<I', len(vendor)) + vendor.encode() + struct.pack('<I', len(comments))
+    for comment in comments:
+        data += struct.pack('<I', len(comment.encode())) + comment.encode()
+    return data
+
+
+COMMENTS = [
+    'TITLE=Fixture Title',
+    'ARTIST=Fixture Artist',
+    'ALBUM=Fixture Album',
+    'GENRE=Ambient',
+    'DATE=2021',
+    'TRACKNUMBER=3',
+    'TRACKTOTAL=12',
+]
+
+
+GAINS = ['REPLAYGAIN_TRACK_GAIN=-6.48 dB', 'REPLAYGAIN_ALBUM_GAIN=-7.25 dB']
+
+
+def flac_file(sample_rate, bits, frame_count):
+    frames = [flac_frame(n, 4096, sample_rate, bits) for n in range(frame_count)]
+    total = 4096 * frame_count
+    md5 = hashlib.md5(b'\x00' * (total * 2 * bits // 8)).digest()
+
+    return (b'fLaC'
+            + flac_block(0, streaminfo(sample_rate, bits, total, len(frames[0]), md5))
+            + flac_block(4, vorbis_comment('reference libFLAC 1.3.4 20220220', COMMENTS + GAINS),
+                         last=True)
+            + b''.join(frames))
+
+
+def flac():
+    write('16bit.flac', flac_file(44100, 16, 10))
+    write('24bit.flac', flac_file(96000, 24, 20))
+
+
+# Ogg
+
+def ogg_crc(data):
+    crc = 0
+    for byte in data:
+        crc ^= byte << 24
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x04C11DB7) if crc & 0x80000000 else (crc << 1)
+            crc &= 0xFFFFFFFF
+    return crc
+
+
+def ogg_page(packets, sequence, granule, header_type=0, serial=0x66617A72):
+    lacing = b''
+    for packet in packets:
+        lacing += b'\xff' * (len(packet) // 255) + bytes([len(packet) % 255])
+    page = bytearray(b'OggS' + bytes([0, header_type])
+                     + struct.pack('<qII', granule, serial, sequence) + b'\x00' * 4
+                     + bytes([len(lacing)]) + lacing + b''.join(packets))
+    page[22:26] = struct.pack('<I', ogg_crc(page))
+    return bytes(page)
+
+
+def opus_link(serial, seconds):
+    pre_skip = 312
+    # an output gain of 1 dB, in Q7.8 fixed point like the R128 gains
+    head = b'OpusHead' + bytes([1, 2]) + struct.pack('<HIhB', pre_skip, 44100, 256, 0)
+    gains = ['R128_TRACK_GAIN=-512', 'R128_ALBUM_GAIN=-384']
+    tags = b'OpusTags' + vorbis_comment('libopus 1.3.1', COMMENTS + gains)
+
+    # 20 ms silent CELT frames, 50 to a page
+    packet = b'\xfc\xff\xfe'
+    pages = [ogg_page([head], 0, 0, 2, serial), ogg_page([tags], 1, 0, 0, serial)]
+    for n in range(seconds):
+        # the last page ends the stream
+        header_type = 4 if n == seconds - 1 else 0
+        pages.append(ogg_page([packet] * 50, 2 + n, 48000 * (n + 1) + pre_skip, header_type, serial))
+
+    return b''.join(pages)
+
+
+def opus():
+    write('opus.opus', opus_link(0x66617A72, 3))
+    # a second link of its own serial, as when a recording is stopped and resumed
+    write('chained.opus', opus_link(0x66617A72, 3) + opus_link(0x66617A73, 2))
+
+
+def vorbis():
+    ident = (b'\x01vorbis' + struct.pack('<IBIiii', 0, 2, 44100, 0, 128000, 0)
+             + bytes([0xB8, 1]))
+    comment = b'\x03vorbis' + vorbis_comment('Xiph.Org libVorbis I 20200704 (Reducing Environment)', COMMENTS) + b'\x01'
+    # a stub setup header, which nothing here decodes
+    setup = b'\x05vorbis' + b'\x00' * 16 + b'\x01'
+
+    pages = [ogg_page([ident], 0, 0, 2), ogg_page([comment, setup], 1, 0)]
+    for n in range(2):
+        header_type = 4 if n == 1 else 0
+        pages.append(ogg_page([b'\x00' * 8] * 40, 2 + n, 44100 * (n + 1), header_type))
+
+    write('vorbis.ogg', b''.join(pages))
+
+
+# MP4
+
+def atom(name, payload):
+    return struct.pack('>I', len(payload) + 8) + name + payload
+
+
+def full(name, payload, version=0, flags=0):
+    return atom(name, struct.pack('>I', (version << 24) | flags) + payload)
+
+
+MATRIX = struct.pack('>9I', 0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000)
+
+
+def esds(channels, object_type=0x40, asc=None):
+    # AAC LC at 44.1 kHz
+    if asc is None:
+        asc = bytes([0x12, 0x00 | (channels << 3)])
+    dec_specific = bytes([0x05, len(asc)]) + asc
+    dec_config = bytes([object_type, 0x15, 0, 0, 0]) + struct.pack('>II', 128000, 128000) + dec_specific
+    dec_config = bytes([0x04, len(dec_config)]) + dec_config
+    sl_config = bytes([0x06, 1, 2])
+    es = struct.pack('>HB', 1, 0) + dec_config + sl_config
+    return full(b'esds', bytes([0x03, len(es)]) + es)
+
+
+def sound_entry(name, channels, bits, sample_rate, extension):
+    return atom(name, b'\x00' * 6 + struct.pack('>H', 1) + b'\x00' * 8
+                + struct.pack('>HHHH', channels, bits, 0, 0)
+                + struct.pack('>I', sample_rate << 16) + extension)
+
+
+def alac_config(channels, bits, sample_rate):
+    return full(b'alac', struct.pack('>IBBBBBBHIII', 4096, 0, bits, 40, 10, 14, channels,
+                                     255, 0, 0, sample_rate))
+
+
+def ilst_item(name, text):
+    return atom(name, atom(b'data', struct.pack('>II', 1, 0) + text.encode()))
+
+
+def ilst_int(name, value):
+    # well-known type 21 is a big-endian signed integer
+    return atom(name, atom(b'data', struct.pack('>II', 21, 0) + bytes([value])))
+
+
+def moof(sequence, sample_durations, sample_size):
+    """A movie fragment of track 1, with a `trun` of each sample's duration."""
+    mfhd = full(b'mfhd', struct.pack('>I', sequence))
+    tfhd = full(b'tfhd', struct.pack('>I', 1), flags=0x020000)
+    tfdt = full(b'tfdt', struct.pack('>I', 0))
+    trun = full(b'trun', struct.pack('>I', len(sample_durations))
+                + b''.join(struct.pack('>II', duration, sample_size) for duration in sample_durations),
+                flags=0x300)
+    return atom(b'moof', mfhd + atom(b'traf', tfhd + tfdt + trun))
+
+
+def fragments(fragment_durations, sample_size):
+    return b''.join(
+        moof(n + 1, durations, sample_size) + atom(b'mdat', b'\x00' * (sample_size * len(durations)))
+        for n, durations in enumerate(fragment_durations))
+
+
+def mp4_file(brand, entry, sample_rate, samples_per_chunk, chunk_count, sample_size,
+             fragment_durations=None, track_name=None):
+    """A file of `chunk_count` samples, or of movie fragments with the given
+    sample durations, and an empty `moov` sample table. A list of sample sizes
+    gives each sample its own, and a track name goes in the track's `udta`."""
+    if fragment_durations is not None:
+        chunk_count = 0
+
+    sample_sizes = sample_size if isinstance(sample_size, list) else [sample_size] * chunk_count
+
+    duration = samples_per_chunk * chunk_count
+    ilst = b''.join([
+        ilst_item(b'\xa9nam', 'Fixture Title'),
+        ilst_item(b'\xa9ART', 'Fixture Artist'),
+        ilst_item(b'\xa9alb', 'Fixture Album'),
+        ilst_item(b'\xa9gen', 'Ambient'),
+        ilst_item(b'\xa9day', '2021'),
+        ilst_int(b'pgap', 1),
+        # the reserved padding, the track number and the total, and more padding
+        atom(b'trkn', atom(b'data', struct.pack('>II', 0, 0) + struct.pack('>HHHH', 0, 3, 12, 0))),
+        ilst_int(b'stik', 1),
+    ])
+
+    ftyp = atom(b'ftyp', brand + struct.pack('>I', 0) + brand + b'isommp42')
+    mvhd = full(b'mvhd', struct.pack('>IIII', 0, 0, 1000, duration * 1000 // sample_rate)
+                + struct.pack('>IH', 0x10000, 0x100) + b'\x00' * 10 + MATRIX + b'\x00' * 24
+                + struct.pack('>I', 2))
+    tkhd = full(b'tkhd', struct.pack('>IIIII', 0, 0, 1, 0, duration * 1000 // sample_rate)
+                + b'\x00' * 8 + struct.pack('>HHHH', 0, 0, 0x100, 0) + MATRIX
+                + struct.pack('>II', 0, 0), flags=1)
+    mdhd = full(b'mdhd', struct.pack('>IIII', 0, 0, sample_rate, duration)
+                + struct.pack('>HH', 0x55C4, 0))
+    hdlr = full(b'hdlr', b'\x00' * 4 + b'soun' + b'\x00' * 12 + b'\x00')
+    smhd = full(b'smhd', b'\x00' * 4)
+    dinf = atom(b'dinf', full(b'dref', struct.pack('>I', 1) + full(b'url ', b'', flags=1)))
+    stsd = full(b'stsd', struct.pack('>I', 1) + entry)
+    stts = full(b'stts', struct.pack('>III', 1, chunk_count, samples_per_chunk))
+    stsc = full(b'stsc', struct.pack('>IIII', 1, 1, 1, 1))
+    if isinstance(sample_size, list):
+        stsz = full(b'stsz', struct.pack('>II', 0, chunk_count)
+                    + b''.join(struct.pack('>I', size) for size in sample_sizes))
+    else:
+        stsz = full(b'stsz', struct.pack('>II', sample_size, chunk_count))
+    udta = atom(b'udta', full(b'meta', full(b'hdlr', b'\x00' * 4 + b'mdirappl' + b'\x00' * 9)
+                              + atom(b'ilst', ilst)))
+
+    def moov(mdat_offset):
+        stco = full(b'stco', struct.pack('>I', chunk_count)
+                    + b''.join(struct.pack('>I', mdat_offset + sum(sample_sizes[:n]))
+                               for n in range(chunk_count)))
+        stbl = atom(b'stbl', stsd + stts + stsc + stsz + stco)
+        mdia = atom(b'mdia', mdhd + hdlr + atom(b'minf', smhd + dinf + stbl))
+        mvex = b''
+        if fragment_durations is not None:
+            mvex = atom(b'mvex', full(b'trex', struct.pack('>IIIII', 1, 1, 0, 0, 0)))
+        trak_udta = b''
+        if track_name is not None:
+            trak_udta = atom(b'udta', atom(b'name', track_name.encode()))
+        return atom(b'moov', mvhd + atom(b'trak', tkhd + mdia + trak_udta) + mvex + udta)
+
+    if fragment_durations is not None:
+        return ftyp + moov(0) + fragments(fragment_durations, sample_size)
+
+    # the sample offsets depend on the size of the moov box that holds them
+    mdat_offset = len(ftyp) + len(moov(0)) + 8
+    mdat = atom(b'mdat', b'\x00' * sum(sample_sizes))
+    return ftyp + moov(mdat_offset) + mdat
+
+
+def dash_segment(fragment_durations, timescale, sample_size):
+    """A DASH media segment, without the initialization segment's `moov`."""
+    styp = atom(b'styp', b'msdh' + struct.pack('>I', 0) + b'msdhmsix')
+    body = fragments(fragment_durations, sample_size)
+
+    # one reference per fragment, each of which starts with a SAP
+    references = b''.join(
+        struct.pack('>III', len(moof(n + 1, durations, sample_size)) + 8 + sample_size * len(durations),
+                    sum(durations), 0x90000000)
+        for n, durations in enumerate(fragment_durations))
+    sidx = full(b'sidx', struct.pack('>IIIIHH', 1, timescale, 0, 0, 0, len(fragment_durations))
+                + references)
+    return styp + sidx + body
+
+
+def mp4():
+    aac = sound_entry(b'mp4a', 2, 16, 44100, esds(2))
+    write('aac.m4a', mp4_file(b'M4A ', aac, 44100, 1024, 86, 6))
+
+    alac = sound_entry(b'alac', 2, 16, 44100, alac_config(2, 16, 44100))
+    write('alac.m4a', mp4_file(b'M4A ', alac, 44100, 4096, 11, 8, track_name='Fixture Track'))
+
+    # HE-AAC, explicitly signalled, of a 24 kHz core that SBR doubles to the
+    # 48 kHz of the sample entry
+    he_aac = sound_entry(b'mp4a', 2, 16, 48000, esds(2, asc=bytes([0x2B, 0x11, 0x88, 0x00])))
+    write('he_aac.m4a', mp4_file(b'M4A ', he_aac, 48000, 2048, 24, [6, 8] * 12))
+
+    # AC-3, which isn't one of the codecs `mp4parse` knows
+    ac3 = sound_entry(b'mp4a', 6, 16, 48000, esds(6, object_type=0xA5))
+    write('ac3.m4a', mp4_file(b'M4A ', ac3, 48000, 1536, 50, 8))
+
+    # three 20 frame fragments, the last cut short
+    durations = [[1024] * 20, [1024] * 20, [1024] * 19 + [512]]
+    write('fragmented.m4a', mp4_file(b'iso6', aac, 44100, 1024, 0, 6, durations))
+    write('segment.m4s', dash_segment(durations, 44100, 6))
+
+
+# WAV
+
+def chunk(chunk_id, data):
+    return chunk_id + struct.pack('<I', len(data)) + data + (b'\x00' if len(data) % 2 else b'')
+
+
+def packed(fields):
+    """Packs `(value, bits)` fields most significant bit first, zero padded."""
+    value = length = 0
+    for field, bits in fields:
+        value, length = value << bits | field, length + bits
+    padding = -length % 8
+    return (value << padding).to_bytes((length + padding) // 8, 'big')
+
+
+def swapped(data):
+    return b''.join(data[i + 1:i + 2] + data[i:i + 1] for i in range(0, len(data), 2))
+
+
+def pcm16_fmt(channels, sample_rate):
+    block_align = channels * 2
+    return struct.pack('<HHIIHH', 1, channels, sample_rate, sample_rate * block_align,
+                       block_align, 16)
+
+
+def dts_frame():
+    """A 5.1 DTS core frame of 512 samples, at 48 kHz and 768 kbps."""
+    header = packed([
+        (0x7FFE8001, 32), (1, 1), (31, 5), (0, 1), (15, 7), (1023, 14),
+        # five channels, 48 kHz, 768 kbps
+        (9, 6), (13, 4), (15, 5), (0, 10),
+        # and the LFE channel
+        (1, 2),
+    ])
+    return header + b'\x00' * (1024 - len(header))
+
+
+def ac3_frame():
+    """A 5.1 AC-3 sync frame at 48 kHz and 448 kbps."""
+    header = packed([
+        (0x0B77, 16), (0, 16), (0, 2), (30, 6), (8, 5), (0, 3),
+        # three front and two surround channels, their mix levels, and the LFE
+        (7, 3), (0, 2), (0, 2), (1, 1),
+    ])
+    return header + b'\x00' * (1792 - len(header))
+
+
+def iec61937_burst(data_type, payload):
+    """An S/PDIF burst of a 1536 sample frame, padded to as many stereo samples."""
+    preamble = struct.pack('<HHHH', 0xF872, 0x4E1F, data_type, len(payload) * 8)
+    burst = preamble + swapped(payload)
+    return burst + b'\x00' * (1536 * 4 - len(burst))
+
+
+IXML = '<?xml version="1.0" encoding="UTF-8"?><BWFXML><SCENE>12A</SCENE><TAKE>3</TAKE></BWFXML>'
+
+
+def wav():
+    channels, sample_rate = 2, 22050
+    block_align = channels * 2
+    fmt = pcm16_fmt(channels, sample_rate)
+    info = chunk(b'LIST', b'INFO' + b''.join(chunk(key, text.encode() + b'\x00') for key, text in [
+        (b'INAM', 'Fixture Title'),
+        (b'IART', 'Fixture Artist'),
+        (b'IPRD', 'Fixture Album'),
+        (b'IGNR', 'Ambient'),
+        (b'ITRK', '03/12'),
+    ]))
+    # a Broadcast Wave chunk, starting at 10:00:00 by its timecode
+    bext = b''.join(text.encode().ljust(size, b'\x00') for text, size in [
+        ('Fixture Description', 256),
+        ('fazer', 32),
+        ('FIXTURE0001', 32),
+        ('2021-06-15', 10),
+        ('08:00:00', 8),
+    ])
+    bext += struct.pack('<QH', 10 * 3600 * sample_rate, 1) + b'\x00' * (64 + 10 + 180)
+    # of an even length, as `hound` doesn't skip the padding of odd chunks
+    bext += b'A=PCM,F=22050,W=16,M=stereo,T=generate\r\n'
+    # half a second of silence
+    data = chunk(b'data', b'\x00' * (sample_rate // 2 * block_align))
+    # and a field recorder's, padded with nulls
+    ixml = IXML.encode().ljust(256, b'\x00')
+    body = b'WAVE' + chunk(b'bext', bext) + chunk(b'fmt ', fmt) + data + info + chunk(b'iXML', ixml)
+
+    write('pcm16.wav', b'RIFF' + struct.pack('<I', len(body)) + body)
+
+    # surround streams in place of 48 kHz stereo PCM: DTS in little-endian
+    # words, and AC-3 in S/PDIF bursts
+    dts = swapped(dts_frame()) * 48
+    body = b'WAVE' + chunk(b'fmt ', pcm16_fmt(2, 48000)) + chunk(b'data', dts)
+    write('dts.wav', b'RIFF' + struct.pack('<I', len(body)) + body)
+
+    ac3 = iec61937_burst(1, ac3_frame()) * 8
+    body = b'WAVE' + chunk(b'fmt ', pcm16_fmt(2, 48000)) + chunk(b'data', ac3)
+    write('ac3.wav', b'RIFF' + struct.pack('<I', len(body)) + body)
+
+
+# AMR
+
+def amr():
+    # fifty 12.2 kbps frames, of 20 ms each
+    write('nb.amr', b'#!AMR\n' + (bytes([7 << 3 | 0x04]) + b'\x00' * 31) * 50)
+
+
+if __name__ == '__main__':
+    mp3()
+    flac()
+    opus()
+    vorbis()
+    mp4()
+    wav()
+    amr()
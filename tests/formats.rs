@@ -0,0 +1,930 @@
+//! Reads a small real file of each supported format, from `tests/fixtures`
+//! (see `generate.py` there), and checks everything `Metadata` makes of it.
+
+use fazer::{
+    detect, detect_format, read_any, read_any_with_options, read_raw_pcm, read_raw_tags,
+    read_stream_info, try_read_any_with_options, write_tag, FazerError, Format, Metadata,
+    ReadOptions,
+};
+
+fn assert_close(actual: Option<f64>, expected: f64) {
+    let actual = actual.expect("missing value");
+    assert!(
+        (actual - expected).abs() < 1e-3,
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+/// Reads a fixture the way `fazer` would, checking it's detected as `format`.
+fn read(data: &[u8], format: &str) -> Metadata {
+    assert_eq!(detect_format(data), Some(format));
+    read_any(data).expect("failed to read the fixture")
+}
+
+fn assert_tags(metadata: &Metadata) {
+    assert_eq!(metadata.title().as_deref(), Some("Fixture Title"));
+    assert_eq!(metadata.artist().as_deref(), Some("Fixture Artist"));
+    assert_eq!(metadata.album().as_deref(), Some("Fixture Album"));
+    assert_eq!(metadata.genre().as_deref(), Some("Ambient"));
+    assert_eq!(metadata.warnings(), &[] as &[String]);
+}
+
+/// Checks the track position, tagged as `03/12` in ID3 and RIFF `INFO`, as `3`
+/// and a separate total in Vorbis comments, and as a binary pair in MP4.
+fn assert_track(metadata: &Metadata) {
+    assert_eq!(metadata.track_number(), Some(3));
+    assert_eq!(metadata.total_tracks(), Some(12));
+    assert_eq!(metadata.disc_number(), None);
+}
+
+/// Writes a custom tag, and checks it doesn't disturb anything else.
+fn rewrite(data: &[u8], format: &str) -> Metadata {
+    let before = read(data, format);
+    let written = write_tag(data, "fixture", "round trip").unwrap();
+    let after = read(&written, format);
+
+    assert_eq!(after.title(), before.title());
+    assert_eq!(after.artist(), before.artist());
+    assert_eq!(after.seconds(), before.seconds());
+    assert_eq!(after.bitrate(), before.bitrate());
+    assert_eq!(after.sample_rate(), before.sample_rate());
+    assert_eq!(after.audio_md5(), before.audio_md5());
+
+    after
+}
+
+/// Writes a custom tag, and checks it reads back into `extra`.
+fn assert_round_trip(data: &[u8], format: &str) {
+    let metadata = rewrite(data, format);
+    let extra = metadata.extra().expect("no extra tags");
+    assert_eq!(extra.get("fixture").map(String::as_str), Some("round trip"));
+}
+
+/// A file of no format gets no tag, rather than an ID3v2 tag in front of it.
+#[test]
+fn write_unrecognized() {
+    // a cheap stand-in for random bytes, with no frame sync at the start
+    let junk = (0..4096_u32)
+        .map(|n| (n.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    assert_ne!(junk[0], 0xFF);
+
+    match write_tag(&junk, "fixture", "round trip") {
+        Err(FazerError::UnrecognizedFormat) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("wrote a tag into junk"),
+    }
+}
+
+/// An ID3v2 tag that can't be read is left alone, rather than replaced.
+#[test]
+fn write_unreadable_id3() {
+    let mut data = include_bytes!("fixtures/cbr.mp3").to_vec();
+    // a major version from the future
+    data[3] = 9;
+
+    match write_tag(&data, "fixture", "round trip") {
+        Err(FazerError::Tag(_)) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("replaced an unreadable tag"),
+    }
+}
+
+/// Files are written to in the format they're read in, even without the magic
+/// at the very start.
+#[test]
+fn write_detected_format() {
+    // an empty ID3v2.4 tag, of 16 bytes of padding, in front of the stream
+    let mut id3_flac = b"ID3\x04\x00\x00\x00\x00\x00\x10".to_vec();
+    id3_flac.resize(26, 0);
+    id3_flac.extend_from_slice(include_bytes!("fixtures/16bit.flac"));
+
+    let written = write_tag(&id3_flac, "fixture", "round trip").unwrap();
+    assert_eq!(written[..26], id3_flac[..26]);
+    assert_round_trip(&id3_flac, "FLAC");
+
+    // `ftyp` turned into a `free` box of the same size, so `moov` comes first
+    let mut moov_first = include_bytes!("fixtures/aac.m4a").to_vec();
+    moov_first[4..8].copy_from_slice(b"free");
+
+    let written = write_tag(&moov_first, "fixture", "round trip").unwrap();
+    let structure = fazer::describe_structure(&written).unwrap();
+    assert_eq!(structure.matches("----").count(), 1);
+    rewrite(&moov_first, "MP4");
+}
+
+#[test]
+fn mp3_cbr() {
+    let data = include_bytes!("fixtures/cbr.mp3");
+    let metadata = read(data, "MP3");
+
+    assert_tags(&metadata);
+    assert_track(&metadata);
+    assert_eq!(metadata.format(), "MP3");
+    // forty frames of 1152 samples
+    assert_close(metadata.seconds(), 40.0 * 1152.0 / 44_100.0);
+    assert_eq!(metadata.bitrate(), Some(128.0));
+    assert_eq!(metadata.min_bitrate(), Some(128.0));
+    assert_eq!(metadata.max_bitrate(), Some(128.0));
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("cbr"));
+    assert_eq!(metadata.sample_rate(), Some(44_100.0));
+    assert_eq!(metadata.channels(), Some(2));
+    assert_eq!(metadata.stereo_mode().as_deref(), Some("joint_stereo"));
+    assert_eq!(metadata.crc_protected(), Some(false));
+    assert_eq!(metadata.has_id3v1(), Some(false));
+    assert_eq!(metadata.has_id3v2(), Some(true));
+    // counted, without a Xing header to declare it
+    assert_eq!(metadata.frame_count(), Some(40));
+
+    // forty frames of 417 bytes, without the odd padding byte that brings a real
+    // stream up to exactly 128 kbps
+    let fast = fazer::read_mp3_fast(data).unwrap();
+    assert_close(fast.seconds(), 40.0 * 417.0 * 8.0 / 128_000.0);
+    assert_eq!(fast.warnings().len(), 1);
+
+    assert_round_trip(data, "MP3");
+}
+
+/// Each field's source names the ID3v2 frame it's from.
+#[test]
+fn mp3_sources() {
+    let (metadata, sources) =
+        fazer::read_mp3_attributed(include_bytes!("fixtures/cbr.mp3")).unwrap();
+    assert_eq!(metadata.artist().as_deref(), Some("Fixture Artist"));
+
+    assert_eq!(sources["artist"], "ID3v2 TPE1");
+    assert_eq!(sources["total_tracks"], "ID3v2 TRCK");
+    assert_eq!(sources["seconds"], "MPEG audio");
+    assert!(!sources.contains_key("comment"));
+}
+
+#[test]
+fn mp3_vbr() {
+    let data = include_bytes!("fixtures/vbr.mp3");
+    let metadata = read(data, "MP3");
+
+    assert_tags(&metadata);
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("vbr"));
+    assert_eq!(metadata.gapless(), Some(false));
+    assert_eq!(metadata.media_type().as_deref(), Some("audiobook"));
+    assert_close(metadata.volume_adjustment_db(), 20.0 * 1.5_f64.log10());
+    // the mean of ten rounds of 128, 160, 192 and 256 kbps, leaving out the
+    // Xing header's frame
+    assert_close(metadata.bitrate(), 7_360.0 / 40.0);
+    assert_eq!(metadata.min_bitrate(), Some(128.0));
+    assert_eq!(metadata.max_bitrate(), Some(256.0));
+    assert_close(metadata.seconds(), 40.0 * 1152.0 / 44_100.0);
+    assert_eq!(metadata.frame_count(), Some(40));
+
+    // only the Xing header's forty frames, without the header's own
+    let fast = fazer::read_mp3_fast(data).unwrap();
+    assert_tags(&fast);
+    assert_close(fast.seconds(), 40.0 * 1152.0 / 44_100.0);
+    assert_eq!(fast.frame_count(), Some(40));
+
+    assert_round_trip(data, "MP3");
+}
+
+#[test]
+fn flac_16_bit() {
+    let data = include_bytes!("fixtures/16bit.flac");
+    let metadata = read(data, "FLAC");
+
+    assert_tags(&metadata);
+    assert_track(&metadata);
+    assert_eq!(metadata.format(), "FLAC");
+    assert_eq!(metadata.date().as_deref(), Some("2021"));
+    assert_eq!(
+        metadata.encoder().as_deref(),
+        Some("reference libFLAC 1.3.4 20220220")
+    );
+    assert_eq!(metadata.track_gain_db(), Some(-6.48));
+    assert_eq!(metadata.album_gain_db(), Some(-7.25));
+    assert_eq!(metadata.output_gain_db(), None);
+    assert_close(metadata.seconds(), 40_960.0 / 44_100.0);
+    assert_eq!(metadata.bit_depth(), Some(16));
+    assert_eq!(metadata.sample_rate(), Some(44_100.0));
+    assert_eq!(metadata.channels(), Some(2));
+    assert_eq!(metadata.channel_layout().as_deref(), Some("stereo"));
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("lossless"));
+    assert_eq!(metadata.truncated(), Some(false));
+    // of 40960 stereo samples of 16 bit silence
+    assert_eq!(
+        metadata.audio_md5().as_deref(),
+        Some("6cc3d8ecd5a9967c9227be8d17b988a6")
+    );
+
+    assert_round_trip(data, "FLAC");
+}
+
+/// Adds a metadata block of `block_type` to a FLAC fixture. After `fLaC` and
+/// STREAMINFO, the VORBIS_COMMENT block is the last one, so this clears its
+/// last block flag and puts the new block behind it.
+fn with_flac_block(data: &[u8], block_type: u8, block: &[u8]) -> Vec<u8> {
+    let comment_len = u32::from_be_bytes([0, data[43], data[44], data[45]]) as usize;
+    let block_at = 42 + 4 + comment_len;
+    let header = (0x80 << 24 | u32::from(block_type) << 24 | block.len() as u32).to_be_bytes();
+
+    let mut tagged = data.to_vec();
+    tagged[42] &= 0x7F;
+    tagged.splice(block_at..block_at, [&header[..], block].concat());
+    tagged
+}
+
+#[test]
+fn flac_padding() {
+    let data = include_bytes!("fixtures/16bit.flac");
+    assert_eq!(fazer::flac_padding_bytes(data), Some(0));
+    assert_eq!(
+        fazer::flac_padding_bytes(include_bytes!("fixtures/cbr.mp3")),
+        None
+    );
+
+    let padded = with_flac_block(data, 1, &[0; 100]);
+    assert_eq!(fazer::flac_padding_bytes(&padded), Some(100));
+}
+
+/// Covers of the days before PICTURE blocks, in an APPLICATION block.
+#[test]
+fn flac_application_cover() {
+    let data = include_bytes!("fixtures/16bit.flac");
+
+    // the signature and IHDR chunk of a 3x2 PNG
+    let png = [
+        &b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"[..],
+        &[0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0],
+    ]
+    .concat();
+    // the application's ID, then a header of its own
+    let block = [&b"imag"[..], &[0; 12], &png].concat();
+
+    let tagged = with_flac_block(data, 2, &block);
+
+    let metadata = read(&tagged, "FLAC");
+    assert_eq!(metadata.cover_width(), Some(3));
+    assert_eq!(metadata.cover_height(), Some(2));
+    assert_eq!(fazer::read_cover(&tagged, usize::MAX).as_ref(), Some(&png));
+    assert_eq!(fazer::read_cover(data, usize::MAX), None);
+
+    // the size of the whole block after the ID counts towards the limit
+    let (cover, warnings) = fazer::read_cover_with_warnings(&tagged, png.len());
+    assert_eq!(cover, None);
+    assert_eq!(warnings.len(), 1);
+}
+
+/// Pictures over the limit are left out, with a warning, before `metaflac`
+/// copies them.
+#[test]
+fn flac_picture_limit() {
+    let data = include_bytes!("fixtures/16bit.flac");
+
+    // a front cover of 100 bytes of PNG data, of no description
+    let image = [0x89; 100];
+    let block = [
+        &3_u32.to_be_bytes()[..],
+        &9_u32.to_be_bytes(),
+        b"image/png",
+        &0_u32.to_be_bytes(),
+        &[0; 16],
+        &(image.len() as u32).to_be_bytes(),
+        &image,
+    ]
+    .concat();
+    let tagged = with_flac_block(data, 6, &block);
+
+    let pictures = fazer::read_all_pictures(&tagged, 100);
+    assert_eq!(pictures.len(), 1);
+    assert_eq!(pictures[0].data, image);
+
+    let (pictures, warnings) = fazer::read_all_pictures_with_warnings(&tagged, 99);
+    assert!(pictures.is_empty());
+    assert_eq!(
+        warnings,
+        ["left out a 100 byte picture, over the 99 byte limit"]
+    );
+    // and the metadata is read as before
+    assert_tags(&read(&tagged, "FLAC"));
+}
+
+#[test]
+fn flac_24_bit() {
+    let data = include_bytes!("fixtures/24bit.flac");
+    let metadata = read(data, "FLAC");
+
+    assert_tags(&metadata);
+    assert_close(metadata.seconds(), 81_920.0 / 96_000.0);
+    assert_eq!(metadata.bit_depth(), Some(24));
+    assert_eq!(metadata.sample_rate(), Some(96_000.0));
+    // lossless beats lossy, and more bits beat fewer
+    assert!(
+        metadata.quality_score()
+            > read_any(include_bytes!("fixtures/16bit.flac"))
+                .unwrap()
+                .quality_score()
+    );
+
+    assert_round_trip(data, "FLAC");
+}
+
+#[test]
+fn opus() {
+    let data = include_bytes!("fixtures/opus.opus");
+    let metadata = read(data, "Ogg");
+
+    assert_tags(&metadata);
+    assert_track(&metadata);
+    assert_eq!(metadata.format(), "OPUS");
+    assert_eq!(metadata.date().as_deref(), Some("2021"));
+    // the final granule position, less the pre-skip, at 48 kHz
+    assert_close(metadata.seconds(), 3.0);
+    assert_eq!(metadata.channels(), Some(2));
+    // R128's -2 and -1.5 dB, 5 dB short of ReplayGain's reference
+    assert_eq!(metadata.track_gain_db(), Some(3.0));
+    assert_eq!(metadata.album_gain_db(), Some(3.5));
+    assert_eq!(metadata.output_gain_db(), Some(1.0));
+}
+
+#[test]
+fn chained_opus() {
+    let data = include_bytes!("fixtures/chained.opus");
+    let metadata = read(data, "Ogg");
+
+    assert_eq!(metadata.title().as_deref(), Some("Fixture Title"));
+    assert_eq!(metadata.format(), "OPUS");
+    // a three second link, then a two second one
+    assert_close(metadata.seconds(), 5.0);
+    assert_eq!(metadata.warnings().len(), 1);
+    assert_eq!(
+        read_stream_info(data).unwrap().seconds(),
+        metadata.seconds()
+    );
+}
+
+#[test]
+fn vorbis() {
+    let data = include_bytes!("fixtures/vorbis.ogg");
+    let metadata = read(data, "Ogg");
+
+    assert_tags(&metadata);
+    assert_eq!(metadata.format(), "VORBIS");
+    assert_close(metadata.seconds(), 2.0);
+    assert_eq!(metadata.channels(), Some(2));
+}
+
+#[test]
+fn aac() {
+    let data = include_bytes!("fixtures/aac.m4a");
+    let metadata = read(data, "MP4");
+
+    assert_tags(&metadata);
+    assert_track(&metadata);
+    assert_eq!(metadata.format(), "AAC");
+    assert_eq!(metadata.date().as_deref(), Some("2021"));
+    assert_eq!(metadata.gapless(), Some(true));
+    assert_eq!(metadata.media_type().as_deref(), Some("music"));
+    assert_eq!(metadata.major_brand().as_deref(), Some("M4A "));
+    assert_eq!(
+        metadata.compatible_brands(),
+        Some(
+            &[
+                String::from("M4A "),
+                String::from("isom"),
+                String::from("mp42")
+            ][..]
+        ),
+    );
+    assert_close(metadata.seconds(), 86.0 * 1024.0 / 44_100.0);
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("cbr"));
+    assert_eq!(metadata.frame_count(), Some(86));
+    // as the `esds` declares it
+    assert_eq!(metadata.max_bitrate(), Some(128.0));
+    assert_eq!(metadata.min_bitrate(), None);
+    // 6 byte samples of 1024 frames
+    assert_close(metadata.bitrate(), 6.0 * 8.0 * 44_100.0 / 1024.0 / 1_000.0);
+    assert_eq!(metadata.sample_rate(), Some(44_100.0));
+    assert_eq!(metadata.channels(), Some(2));
+    assert_eq!(metadata.truncated(), Some(false));
+
+    // a single, default title
+    assert_eq!(
+        fazer::read_mp4_localized_titles(data),
+        Some(vec![(String::from("und"), String::from("Fixture Title"))])
+    );
+
+    // freeform MP4 items aren't read into `extra`
+    rewrite(data, "MP4");
+}
+
+#[test]
+fn he_aac() {
+    let metadata = read(include_bytes!("fixtures/he_aac.m4a"), "MP4");
+
+    assert_eq!(metadata.format(), "AAC");
+    // the core rate of the `esds`, not the sample entry's
+    assert_eq!(metadata.sample_rate(), Some(24_000.0));
+    assert_close(metadata.seconds(), 24.0 * 2048.0 / 48_000.0);
+    // samples of 6 and 8 bytes in turn
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("vbr"));
+    assert_eq!(metadata.frame_count(), Some(24));
+    assert_close(metadata.bitrate(), 168.0 * 8.0 / 1.024 / 1_000.0);
+}
+
+#[test]
+fn alac() {
+    let data = include_bytes!("fixtures/alac.m4a");
+    let metadata = read(data, "MP4");
+
+    assert_tags(&metadata);
+    assert_eq!(metadata.format(), "ALAC");
+    assert_close(metadata.seconds(), 11.0 * 4096.0 / 44_100.0);
+    assert_eq!(metadata.bit_depth(), Some(16));
+    assert_eq!(metadata.bitrate_mode().as_deref(), Some("lossless"));
+
+    // freeform MP4 items aren't read into `extra`
+    rewrite(data, "MP4");
+
+    // the item goes in `moov/udta/meta/ilst`, and not in the track's `udta`
+    let written = write_tag(data, "fixture", "round trip").unwrap();
+    let structure = fazer::describe_structure(&written).unwrap();
+    assert!(structure.contains("\n    udta (29 bytes)\n      name (21 bytes)\n"));
+    assert_eq!(structure.matches("meta").count(), 1);
+    assert_eq!(structure.matches("----").count(), 1);
+}
+
+#[test]
+fn mp4_unknown_codec() {
+    let data = include_bytes!("fixtures/ac3.m4a");
+    let metadata = read(data, "MP4");
+
+    assert_tags(&metadata);
+    assert_eq!(metadata.format(), "UNKNOWN");
+    assert_close(metadata.seconds(), 50.0 * 1536.0 / 48_000.0);
+    assert_eq!(metadata.sample_rate(), Some(48_000.0));
+    assert_eq!(metadata.channels(), Some(6));
+}
+
+#[test]
+fn fragmented_mp4() {
+    // three fragments of 1024 sample frames, the last one a half frame short
+    let seconds = (60.0 * 1024.0 - 512.0) / 44_100.0;
+
+    let metadata = read(include_bytes!("fixtures/fragmented.m4a"), "MP4");
+    assert_tags(&metadata);
+    assert_eq!(metadata.format(), "AAC");
+    assert_close(metadata.seconds(), seconds);
+
+    // a DASH media segment, which only has its segment index to go by
+    let metadata = read(include_bytes!("fixtures/segment.m4s"), "MP4");
+    assert_eq!(metadata.format(), "UNKNOWN");
+    assert_close(metadata.seconds(), seconds);
+}
+
+/// A preferred order of readers doesn't run them on files they don't claim.
+#[test]
+fn read_order() {
+    let mp3 = include_bytes!("fixtures/cbr.mp3");
+    let flac = include_bytes!("fixtures/16bit.flac");
+
+    assert_eq!(fazer::read_best(mp3).unwrap().format(), "MP3");
+    assert_eq!(fazer::read_best(flac).unwrap().format(), "FLAC");
+    assert_eq!(
+        fazer::read_in_order(mp3, &["FLAC"]).unwrap().format(),
+        "MP3"
+    );
+    let metadata = fazer::read_in_order(flac, &["wav", "MP4"]).unwrap();
+    assert_eq!(metadata.format(), "FLAC");
+}
+
+/// Strict reads fail rather than leave the format, duration or sample rate out.
+#[test]
+fn strict() {
+    let options = ReadOptions {
+        strict: true,
+        ..ReadOptions::default()
+    };
+
+    let metadata = try_read_any_with_options(include_bytes!("fixtures/aac.m4a"), &options);
+    assert_eq!(metadata.unwrap().format(), "AAC");
+
+    // a DASH segment has only its duration to go by
+    match try_read_any_with_options(include_bytes!("fixtures/segment.m4s"), &options) {
+        Err(FazerError::Incomplete(fields)) => assert_eq!(fields, ["format", "sample rate"]),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("a strict read of a DASH segment succeeded"),
+    }
+    assert!(read_any_with_options(include_bytes!("fixtures/segment.m4s"), &options).is_none());
+}
+
+#[test]
+fn wav() {
+    let data = include_bytes!("fixtures/pcm16.wav");
+    let metadata = read(data, "WAV");
+
+    assert_tags(&metadata);
+    assert_track(&metadata);
+    assert_eq!(metadata.format(), "WAV");
+    assert_close(metadata.seconds(), 0.5);
+    // 22.05 kHz, 16 bit stereo
+    assert_close(metadata.bitrate(), 705.6);
+    assert_eq!(metadata.bit_depth(), Some(16));
+    assert_eq!(metadata.sample_format().as_deref(), Some("int"));
+    assert_eq!(metadata.sample_rate(), Some(22_050.0));
+    assert_eq!(metadata.truncated(), Some(false));
+}
+
+#[test]
+fn wav_bext() {
+    let bext = fazer::read_wav_bext(include_bytes!("fixtures/pcm16.wav")).unwrap();
+
+    assert_eq!(bext.description.as_deref(), Some("Fixture Description"));
+    assert_eq!(bext.originator.as_deref(), Some("fazer"));
+    assert_eq!(bext.originator_reference.as_deref(), Some("FIXTURE0001"));
+    assert_eq!(bext.origination_date.as_deref(), Some("2021-06-15"));
+    assert_eq!(bext.origination_time.as_deref(), Some("08:00:00"));
+    assert_eq!(bext.time_reference, 10 * 3600 * 22_050);
+    assert_eq!(bext.time_reference_seconds, Some(36_000.0));
+    assert_eq!(bext.version, 1);
+    assert_eq!(
+        bext.coding_history.as_deref(),
+        Some("A=PCM,F=22050,W=16,M=stereo,T=generate")
+    );
+
+    assert_eq!(
+        fazer::read_wav_bext(include_bytes!("fixtures/dts.wav")),
+        None
+    );
+}
+
+#[test]
+fn wav_ixml() {
+    let ixml = fazer::read_wav_ixml(include_bytes!("fixtures/pcm16.wav")).unwrap();
+    assert!(ixml.starts_with("<?xml"));
+    assert!(ixml.ends_with("<SCENE>12A</SCENE><TAKE>3</TAKE></BWFXML>"));
+
+    assert_eq!(
+        fazer::read_wav_ixml(include_bytes!("fixtures/dts.wav")),
+        None
+    );
+}
+
+/// DTS and AC-3 streams in place of PCM take their parameters from the stream,
+/// rather than from the PCM carrying them.
+#[test]
+fn wav_surround() {
+    for (data, format, bitrate) in [
+        (&include_bytes!("fixtures/dts.wav")[..], "DTS", 768.0),
+        (&include_bytes!("fixtures/ac3.wav")[..], "AC3", 448.0),
+    ] {
+        let metadata = read(data, "WAV");
+
+        assert_eq!(metadata.format(), format);
+        assert_close(metadata.seconds(), 0.256);
+        assert_eq!(metadata.channels(), Some(6));
+        assert_eq!(metadata.sample_rate(), Some(48_000.0));
+        assert_eq!(metadata.bitrate(), Some(bitrate));
+        assert_eq!(metadata.bitrate_mode().as_deref(), Some("cbr"));
+        assert_eq!(metadata.bit_depth(), None);
+    }
+}
+
+/// A DTS header, in the 14 bit packing of DTS CDs, turns up in PCM by chance,
+/// starting with the stereo sample (8191, -6144). Without a frame after it,
+/// the PCM is read as it is.
+#[test]
+fn wav_pcm_like_dts() {
+    let dts = include_bytes!("fixtures/dts.wav");
+    let dts = &dts[dts.windows(4).position(|id| id == b"data").unwrap() + 8..];
+
+    // the header, in big-endian words rather than the fixture's little-endian
+    let header = dts[..16]
+        .chunks_exact(2)
+        .flat_map(|word| [word[1], word[0]])
+        .collect::<Vec<_>>();
+    let bit = |bit: usize| u16::from(header[bit / 8] >> (7 - bit % 8) & 1);
+
+    // 14 bits to each sign-extended little-endian word
+    let mut packed = Vec::new();
+    for start in (0..header.len() * 8 - 14).step_by(14) {
+        let word = (start..start + 14).fold(0, |word, n| word << 1 | bit(n));
+        let word = if word & 0x2000 != 0 {
+            word | 0xC000
+        } else {
+            word
+        };
+        packed.extend(word.to_le_bytes());
+    }
+    assert_eq!(packed[..4], [0xFF, 0x1F, 0x00, 0xE8]);
+
+    let mut data = include_bytes!("fixtures/pcm16.wav").to_vec();
+    let start = data.windows(4).position(|id| id == b"data").unwrap() + 8;
+    data[start..start + packed.len()].copy_from_slice(&packed);
+
+    let metadata = read(&data, "WAV");
+    assert_eq!(metadata.format(), "WAV");
+    assert_eq!(metadata.channels(), Some(2));
+    assert_eq!(metadata.sample_rate(), Some(22_050.0));
+}
+
+#[test]
+fn amr() {
+    let data = include_bytes!("fixtures/nb.amr");
+    let metadata = read(data, "AMR");
+
+    assert_eq!(metadata.format(), "AMR");
+    assert_close(metadata.seconds(), 1.0);
+    assert_eq!(metadata.sample_rate(), Some(8_000.0));
+    assert_eq!(metadata.channels(), Some(1));
+}
+
+/// The samples of a WAV file, without their header, read the same as the file.
+#[test]
+fn raw_pcm() {
+    let wav = read(include_bytes!("fixtures/pcm16.wav"), "WAV");
+    let (start, end) = wav.audio_range().unwrap();
+    let data = &include_bytes!("fixtures/pcm16.wav")[start as usize..end as usize];
+
+    let metadata = read_raw_pcm(data, 22_050, 2, 16);
+    assert_eq!(metadata.format(), "WAV");
+    assert_close(metadata.seconds(), wav.seconds().unwrap());
+    assert_eq!(metadata.bitrate(), wav.bitrate());
+    assert_eq!(metadata.audio_md5(), wav.audio_md5());
+    assert!(metadata.warnings().is_empty());
+
+    let metadata = read_raw_pcm(&data[1..], 22_050, 2, 16);
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+/// Raw tags keep each tagger's own keys, unless they're asked to be normalized.
+#[test]
+fn raw_tags() {
+    let fixtures: [(&[u8], &str); 5] = [
+        (include_bytes!("fixtures/cbr.mp3"), "TIT2"),
+        (include_bytes!("fixtures/16bit.flac"), "TITLE"),
+        (include_bytes!("fixtures/vorbis.ogg"), "TITLE"),
+        (include_bytes!("fixtures/aac.m4a"), "\u{a9}nam"),
+        (include_bytes!("fixtures/pcm16.wav"), "INAM"),
+    ];
+
+    for (data, key) in fixtures {
+        let title = (String::from(key), String::from("Fixture Title"));
+        assert!(read_raw_tags(data, false).unwrap().contains(&title));
+
+        let title = (key.to_lowercase(), String::from("Fixture Title"));
+        assert!(read_raw_tags(data, true).unwrap().contains(&title));
+    }
+}
+
+#[test]
+fn duration_precision() {
+    let options = ReadOptions {
+        duration_precision: Some(2),
+        ..ReadOptions::default()
+    };
+
+    // 1.0449 and 0.9288 seconds
+    let mp3 = read_any_with_options(include_bytes!("fixtures/cbr.mp3"), &options).unwrap();
+    assert_eq!(mp3.seconds(), Some(1.04));
+    let flac = read_any_with_options(include_bytes!("fixtures/16bit.flac"), &options).unwrap();
+    assert_eq!(flac.seconds(), Some(0.93));
+}
+
+/// Past the scan limit, MP3 files only have a sample of their frames read.
+#[test]
+fn mp3_scan_limit() {
+    let options = ReadOptions {
+        mp3_scan_limit: Some(0),
+        ..ReadOptions::default()
+    };
+
+    // estimated from the bytes per second of the frames, which are all there is
+    let cbr = read_any_with_options(include_bytes!("fixtures/cbr.mp3"), &options).unwrap();
+    assert_eq!(cbr.title().as_deref(), Some("Fixture Title"));
+    assert_close(cbr.seconds(), 40.0 * 1152.0 / 44_100.0);
+    assert_eq!(cbr.frame_count(), None);
+    assert_eq!(cbr.warnings().len(), 1);
+
+    // the Xing header's forty frames, without the header's own
+    let vbr = read_any_with_options(include_bytes!("fixtures/vbr.mp3"), &options).unwrap();
+    assert_close(vbr.seconds(), 40.0 * 1152.0 / 44_100.0);
+    assert_eq!(vbr.frame_count(), Some(40));
+    assert_eq!(vbr.bitrate_mode().as_deref(), Some("vbr"));
+    assert!(vbr.warnings().is_empty());
+}
+
+#[test]
+fn detect_codecs() {
+    let fixtures: [(&[u8], Format, f32); 9] = [
+        (include_bytes!("fixtures/cbr.mp3"), Format::Mp3, 0.75),
+        (include_bytes!("fixtures/16bit.flac"), Format::Flac, 1.0),
+        (include_bytes!("fixtures/opus.opus"), Format::Opus, 1.0),
+        (include_bytes!("fixtures/vorbis.ogg"), Format::Vorbis, 1.0),
+        // only a guess, as `mp4a` could hold another codec
+        (include_bytes!("fixtures/aac.m4a"), Format::Aac, 0.75),
+        (include_bytes!("fixtures/alac.m4a"), Format::Alac, 1.0),
+        (include_bytes!("fixtures/segment.m4s"), Format::Unknown, 0.5),
+        (include_bytes!("fixtures/dts.wav"), Format::Dts, 1.0),
+        (include_bytes!("fixtures/ac3.wav"), Format::Ac3, 1.0),
+    ];
+
+    for (data, format, confidence) in fixtures {
+        let result = detect(data).unwrap();
+        assert_eq!(Some(result.container), detect_format(data));
+        assert_eq!((result.format, result.confidence), (format, confidence));
+    }
+}
+
+/// The audio range starts where each format's audio does, past the tags.
+#[test]
+fn audio_ranges() {
+    use std::convert::TryInto;
+
+    let fixtures: [(&[u8], &[u8]); 6] = [
+        // the frame sync
+        (include_bytes!("fixtures/cbr.mp3"), b"\xFF\xFB"),
+        (include_bytes!("fixtures/16bit.flac"), b"\xFF\xF8"),
+        // the chunk and atom headers right in front
+        (include_bytes!("fixtures/pcm16.wav"), b"data"),
+        (include_bytes!("fixtures/aac.m4a"), b"mdat"),
+        // the frame header of a 12.2 kbps frame
+        (include_bytes!("fixtures/nb.amr"), b"\x3C"),
+        (include_bytes!("fixtures/opus.opus"), b""),
+    ];
+
+    for (data, marker) in fixtures {
+        let metadata = read_any(data).unwrap();
+        let (start, end) = match metadata.audio_range() {
+            Some((start, end)) => (start as usize, end as usize),
+            None => {
+                assert_eq!(metadata.format(), "OPUS");
+                continue;
+            }
+        };
+
+        // and ends where the chunk's or atom's size says, or the file does
+        let size = |offset: usize| data[offset..offset + 4].try_into().unwrap();
+        match marker {
+            b"data" => {
+                assert_eq!(&data[start - 8..start - 4], marker);
+                assert_eq!(end - start, u32::from_le_bytes(size(start - 4)) as usize);
+            }
+            b"mdat" => {
+                assert_eq!(&data[start - 4..start], marker);
+                assert_eq!(
+                    end - start + 8,
+                    u32::from_be_bytes(size(start - 8)) as usize
+                );
+            }
+            _ => {
+                assert!(data[start..].starts_with(marker));
+                assert_eq!(end, data.len());
+            }
+        }
+    }
+}
+
+/// `read_stream_info` skips the tags, but has to agree with the full readers.
+#[test]
+fn stream_info_agrees() {
+    let fixtures: [&[u8]; 10] = [
+        include_bytes!("fixtures/cbr.mp3"),
+        include_bytes!("fixtures/vbr.mp3"),
+        include_bytes!("fixtures/16bit.flac"),
+        include_bytes!("fixtures/24bit.flac"),
+        include_bytes!("fixtures/opus.opus"),
+        include_bytes!("fixtures/vorbis.ogg"),
+        include_bytes!("fixtures/aac.m4a"),
+        include_bytes!("fixtures/alac.m4a"),
+        include_bytes!("fixtures/pcm16.wav"),
+        include_bytes!("fixtures/nb.amr"),
+    ];
+
+    for data in fixtures {
+        let full = read_any(data).unwrap();
+        let info = read_stream_info(data).unwrap();
+
+        assert_eq!(info.title(), None);
+        assert_eq!(info.seconds(), full.seconds());
+        assert_eq!(info.channels(), full.channels());
+        assert_eq!(info.sample_rate(), full.sample_rate());
+        assert_eq!(info.bit_depth(), full.bit_depth());
+    }
+}
+
+/// With the `hash` feature, formats without a checksum of their own get an MD5
+/// of their encoded audio.
+#[cfg(feature = "hash")]
+#[test]
+fn hashes_audio() {
+    let fixtures: [(&[u8], &str); 2] = [
+        // the frames after the ID3v2 tag
+        (
+            include_bytes!("fixtures/cbr.mp3"),
+            "8cdcbeaa08d091e96878a4c3ee3be3a7",
+        ),
+        // the `data` chunk's 44100 bytes of silence
+        (
+            include_bytes!("fixtures/pcm16.wav"),
+            "bd881f582a1efb5094ca6681ec0b48e9",
+        ),
+    ];
+
+    for (data, md5) in fixtures {
+        assert_eq!(read_any(data).unwrap().audio_md5().as_deref(), Some(md5));
+    }
+}
+
+/// A read within its time budget returns as usual, and one past it gives up.
+#[cfg(feature = "std")]
+#[test]
+fn timeout() {
+    use std::time::Duration;
+
+    let data = include_bytes!("fixtures/16bit.flac");
+    let metadata = fazer::read_with_timeout(data, Duration::from_secs(60)).unwrap();
+    assert_eq!(metadata.unwrap().format(), "FLAC");
+
+    match fazer::read_with_timeout(data, Duration::ZERO) {
+        Err(FazerError::Timeout(timeout)) => assert_eq!(timeout, Duration::ZERO),
+        Err(err) => panic!("unexpected error: {}", err),
+        // the read can beat even a zero timeout
+        Ok(metadata) => assert_eq!(metadata.unwrap().format(), "FLAC"),
+    }
+
+    // the only test of `read_with_timeout`, so nothing else sees the cap
+    fazer::set_max_timeout_workers(0);
+    let busy = fazer::read_with_timeout(data, Duration::from_secs(60));
+    fazer::set_max_timeout_workers(usize::MAX);
+    match busy {
+        Err(FazerError::Busy) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("read past the cap on workers"),
+    }
+}
+
+/// The readers share no state, so reading on several threads at once gives
+/// the same results as reading one file after another.
+#[test]
+fn reads_across_threads() {
+    let fixtures: [&[u8]; 5] = [
+        include_bytes!("fixtures/vbr.mp3"),
+        include_bytes!("fixtures/24bit.flac"),
+        include_bytes!("fixtures/vorbis.ogg"),
+        include_bytes!("fixtures/alac.m4a"),
+        include_bytes!("fixtures/pcm16.wav"),
+    ];
+
+    std::thread::scope(|scope| {
+        let threads = fixtures.map(|data| scope.spawn(move || read_any(data).unwrap()));
+
+        for (data, thread) in fixtures.iter().zip(threads) {
+            let threaded = thread.join().unwrap();
+            let metadata = read_any(data).unwrap();
+
+            assert_eq!(threaded.format(), metadata.format());
+            assert_eq!(threaded.title(), metadata.title());
+            assert_eq!(threaded.seconds(), metadata.seconds());
+            assert_eq!(threaded.bitrate(), metadata.bitrate());
+        }
+    });
+}
+
+/// The FLAC and WAV fixtures hold real, decodable silence.
+#[cfg(feature = "decode")]
+#[test]
+fn decodes_silence() {
+    let fixtures: [&[u8]; 3] = [
+        include_bytes!("fixtures/16bit.flac"),
+        include_bytes!("fixtures/24bit.flac"),
+        include_bytes!("fixtures/pcm16.wav"),
+    ];
+
+    for data in fixtures {
+        assert_eq!(fazer::read_trimmed_duration(data, -60.0), Some(0.0));
+    }
+}
+
+/// The structure outline covers each container's building blocks.
+#[test]
+fn structure() {
+    let mp4 = fazer::describe_structure(include_bytes!("fixtures/aac.m4a")).unwrap();
+    assert!(mp4.starts_with("ftyp (28 bytes)\nmoov (1194 bytes)\n  mvhd (108 bytes)\n"));
+    assert!(mp4.contains("\n        ©nam (37 bytes)\n          data (29 bytes)\n"));
+    assert!(mp4.ends_with("\nmdat (524 bytes)\n"));
+
+    let wav = fazer::describe_structure(include_bytes!("fixtures/pcm16.wav")).unwrap();
+    assert!(wav.contains("\n  LIST INFO (110 bytes)\n    INAM (22 bytes)\n"));
+
+    let flac = fazer::describe_structure(include_bytes!("fixtures/16bit.flac")).unwrap();
+    assert_eq!(
+        flac,
+        "fLaC (4 bytes)\n  STREAMINFO (38 bytes)\n  VORBIS_COMMENT (247 bytes)\n\
+         audio frames (140 bytes)\n"
+    );
+
+    let ogg = fazer::describe_structure(include_bytes!("fixtures/opus.opus")).unwrap();
+    assert!(ogg.starts_with("OggS stream 66617A72, granule 0, first page (47 bytes)\n"));
+
+    assert!(fazer::describe_structure(include_bytes!("fixtures/cbr.mp3")).is_none());
+}